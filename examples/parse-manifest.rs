@@ -1,7 +1,8 @@
 #![allow(clippy::uninlined_format_args)]
 
 use clap::Parser;
-use rustup_toolchain_manifest::{InstallSpec, Manifest, Toolchain};
+use rustup_toolchain_manifest::host::detect_host;
+use rustup_toolchain_manifest::{ComponentName, InstallSpec, Manifest, Toolchain};
 use std::path::PathBuf;
 use std::str::FromStr;
 use target_lexicon::Triple;
@@ -18,12 +19,11 @@ fn main() {
     let input_file = cli.input_file;
     let content = std::fs::read_to_string(input_file).expect("Failed to read input file");
     let manifest = Manifest::try_from(content.as_str()).expect("Failed to parse manifest");
-    let spec = InstallSpec {
-        profile: "minimal".into(),
-        components: ["clippy", "rust-src"].into_iter().map(String::from).collect(),
-        targets: ["wasm32-unknown-unknown"].into_iter().map(String::from).collect(),
-    };
-    let host = Triple::from_str("x86_64-unknown-linux-gnu").expect("Failed to parse triple");
+    let spec = InstallSpec::new("minimal")
+        .with_component(ComponentName::new("clippy"))
+        .with_component(ComponentName::new("rust-src"))
+        .with_target(Triple::from_str("wasm32-unknown-unknown").expect("Failed to parse triple"));
+    let host = detect_host();
     println!("For target {}, finding the following toolchain:\n{:#?}", host, spec);
     println!();
     let packages = manifest
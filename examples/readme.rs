@@ -1,6 +1,6 @@
 use rustup_toolchain_manifest::{Manifest, Toolchain, InstallSpec};
-use std::collections::HashSet;
 use std::str::FromStr;
+use target_lexicon::Triple;
 
 fn main() {
     let toolchain = Toolchain::from_str("nightly-x86_64-unknown-linux-gnu").expect("Failed to parse toolchain");
@@ -15,11 +15,8 @@ fn main() {
     println!("Successfully retrieved manifest of {} bytes.", manifest.len());
 
     let manifest = Manifest::try_from(manifest.as_str()).expect("Failed to parse manifest");
-    let install_spec = InstallSpec {
-        profile: "default".into(),
-        components: HashSet::new(),
-        targets: ["wasm32-unknown-unknown"].into_iter().map(String::from).collect(),
-    };
+    let install_spec = InstallSpec::new("default")
+        .with_target(Triple::from_str("wasm32-unknown-unknown").expect("Failed to parse triple"));
 
     let target = toolchain.host.expect("Host missing for previously specified toolchain");
     println!(
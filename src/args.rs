@@ -0,0 +1,289 @@
+use crate::host::{detect_host, HostError};
+use crate::spec::{ComponentName, ParseError as ComponentParseError};
+use crate::toolchain::{ParseError as ToolchainParseError, PartialToolchain};
+use crate::{InstallSpec, Toolchain};
+use std::str::FromStr;
+use target_lexicon::Triple;
+use thiserror::Error;
+
+/// The profile Rustup installs when none is given
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Errors which can occur while parsing install arguments
+#[derive(Clone, Debug, Error)]
+pub enum ArgsError {
+    /// An option was not recognised
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+
+    /// An option which takes a value was not given one
+    #[error("Option {0} requires a value")]
+    MissingValue(String),
+
+    /// No toolchain was given, either as an argument or in `RUSTUP_TOOLCHAIN`
+    #[error("No toolchain specified")]
+    MissingToolchain,
+
+    /// A toolchain name was invalid
+    #[error("Invalid toolchain: {0}")]
+    Toolchain(#[from] ToolchainParseError),
+
+    /// An abbreviated host in a toolchain name could not be completed
+    #[error("Invalid toolchain host: {0}")]
+    Host(#[from] HostError),
+
+    /// A component name was invalid
+    #[error("{0}")]
+    Component(#[from] ComponentParseError),
+
+    /// A target triple was invalid
+    #[error("Invalid target: {0}")]
+    Target(#[from] target_lexicon::ParseError),
+}
+
+/// The arguments of a `rustup toolchain install` invocation, so that tools
+/// can accept the same interface as Rustup while resolving installs with this
+/// crate
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallArgs {
+    /// The toolchains to install, with their hosts completed
+    pub toolchains: Vec<Toolchain>,
+
+    /// The profile, components and targets to install
+    pub spec: InstallSpec,
+
+    /// Whether an older nightly may be installed to obtain every requested
+    /// component (`--allow-downgrade`)
+    pub allow_downgrade: bool,
+
+    /// Whether to install even if some components are missing (`--force`)
+    pub force: bool,
+
+    /// Whether to install toolchains for hosts other than the current one
+    /// (`--force-non-host`)
+    pub force_non_host: bool,
+
+    /// Whether to skip updating Rustup itself (`--no-self-update`)
+    pub no_self_update: bool,
+
+    /// The distribution server from `RUSTUP_DIST_SERVER`, if set
+    pub dist_server: Option<String>,
+}
+
+impl InstallArgs {
+    /// Parses the arguments following `rustup toolchain install`, using the
+    /// process environment and the detected host
+    pub fn from_process_args<I, S>(args: I) -> Result<InstallArgs, ArgsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        InstallArgs::parse(args, &detect_host(), &|name| std::env::var(name).ok())
+    }
+
+    /// Parses the arguments following `rustup toolchain install`.
+    ///
+    /// As with Rustup, `-c`/`--component` and `-t`/`--target` accept values
+    /// separated by commas or given as several arguments. Unless the value is
+    /// given inline (e.g. `--component=clippy` or `-cclippy`), they consume
+    /// arguments up to the next option, so toolchain names following them
+    /// must be separated with `--`. Abbreviated hosts in toolchain names are
+    /// completed against `host`, and toolchains without a host are given
+    /// it. If no toolchain is given, `RUSTUP_TOOLCHAIN` is used, and
+    /// `RUSTUP_DIST_SERVER` sets the distribution server, where `env` reads
+    /// environment variables.
+    pub fn parse<I, S>(args: I, host: &Triple, env: &dyn Fn(&str) -> Option<String>) -> Result<InstallArgs, ArgsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_string()).collect();
+        let mut result = InstallArgs {
+            toolchains: Vec::new(),
+            spec: InstallSpec::new(DEFAULT_PROFILE),
+            allow_downgrade: false,
+            force: false,
+            force_non_host: false,
+            no_self_update: false,
+            dist_server: env("RUSTUP_DIST_SERVER").filter(|server| !server.is_empty()),
+        };
+        let mut names = Vec::new();
+        let mut index = 0;
+        while index < args.len() {
+            let arg = args[index].as_str();
+            index += 1;
+            if arg == "--" {
+                names.extend(args[index..].iter().cloned());
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                names.push(arg.to_string());
+                continue;
+            }
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) if arg.starts_with("--") => (option, Some(value.to_string())),
+                _ if !arg.starts_with("--") && arg.len() > 2 && arg.is_char_boundary(2) => {
+                    let value = &arg[2..];
+                    (&arg[..2], Some(value.strip_prefix('=').unwrap_or(value).to_string()))
+                }
+                _ => (arg, None),
+            };
+            match option {
+                "--allow-downgrade" | "--force" | "--force-non-host" | "--no-self-update" if inline.is_some() => {
+                    return Err(ArgsError::UnknownOption(arg.to_string()));
+                }
+                "--allow-downgrade" => result.allow_downgrade = true,
+                "--force" => result.force = true,
+                "--force-non-host" => result.force_non_host = true,
+                "--no-self-update" => result.no_self_update = true,
+                "--profile" => {
+                    let value = inline.or_else(|| {
+                        let value = args.get(index).filter(|value| !value.starts_with('-')).cloned();
+                        index += usize::from(value.is_some());
+                        value
+                    });
+                    result.spec.profile = value
+                        .filter(|value| !value.is_empty())
+                        .ok_or_else(|| ArgsError::MissingValue(option.to_string()))?;
+                }
+                "-c" | "--component" | "-t" | "--target" => {
+                    let values = if let Some(value) = inline {
+                        vec![value]
+                    } else {
+                        let following = args[index..].iter().take_while(|value| !value.starts_with('-'));
+                        let values: Vec<String> = following.cloned().collect();
+                        index += values.len();
+                        values
+                    };
+                    let values: Vec<&str> = values
+                        .iter()
+                        .flat_map(|value| value.split(','))
+                        .filter(|v| !v.is_empty())
+                        .collect();
+                    if values.is_empty() {
+                        return Err(ArgsError::MissingValue(option.to_string()));
+                    }
+                    for value in values {
+                        if matches!(option, "-c" | "--component") {
+                            result.spec.components.insert(ComponentName::from_str(value)?);
+                        } else {
+                            result.spec.targets.insert(Triple::from_str(value)?);
+                        }
+                    }
+                }
+                _ => return Err(ArgsError::UnknownOption(arg.to_string())),
+            }
+        }
+        if names.is_empty() {
+            names.extend(env("RUSTUP_TOOLCHAIN").filter(|name| !name.is_empty()));
+        }
+        if names.is_empty() {
+            return Err(ArgsError::MissingToolchain);
+        }
+        for name in names {
+            let mut toolchain = PartialToolchain::from_str(&name)?.complete(host)?;
+            toolchain.host.get_or_insert_with(|| host.clone());
+            result.toolchains.push(toolchain);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<InstallArgs, ArgsError> {
+        let host = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        InstallArgs::parse(args, &host, &|_| None)
+    }
+
+    fn components(args: &InstallArgs) -> Vec<String> {
+        let mut components: Vec<String> = args.spec.components.iter().map(ToString::to_string).collect();
+        components.sort();
+        components
+    }
+
+    fn toolchains(args: &InstallArgs) -> Vec<String> {
+        args.toolchains.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn list_options_consume_following_arguments() {
+        let args = parse(&[
+            "-c",
+            "clippy,rustfmt",
+            "rust-src",
+            "--profile",
+            "minimal",
+            "--",
+            "nightly",
+        ])
+        .unwrap();
+        assert_eq!(components(&args), ["clippy", "rust-src", "rustfmt"]);
+        assert_eq!(args.spec.profile, "minimal");
+        assert_eq!(toolchains(&args), ["nightly-x86_64-unknown-linux-gnu"]);
+
+        let args = parse(&["stable", "-t", "wasm32-unknown-unknown", "--force"]).unwrap();
+        assert_eq!(args.spec.targets.len(), 1);
+        assert!(args.force);
+        assert_eq!(toolchains(&args), ["stable-x86_64-unknown-linux-gnu"]);
+    }
+
+    #[test]
+    fn inline_values_do_not_consume_following_arguments() {
+        for option in ["--component=clippy", "-cclippy", "-c=clippy"] {
+            let args = parse(&[option, "nightly", "beta-2023-06-01"]).unwrap();
+            assert_eq!(components(&args), ["clippy"], "{}", option);
+            assert_eq!(
+                toolchains(&args),
+                [
+                    "nightly-x86_64-unknown-linux-gnu",
+                    "beta-2023-06-01-x86_64-unknown-linux-gnu"
+                ]
+            );
+        }
+        let args = parse(&["--profile=minimal", "nightly"]).unwrap();
+        assert_eq!(args.spec.profile, "minimal");
+        assert_eq!(toolchains(&args), ["nightly-x86_64-unknown-linux-gnu"]);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        for args in [
+            &["--profile=", "nightly"][..],
+            &["nightly", "--profile"],
+            &["--component=", "nightly"],
+            &["nightly", "-c", "--force"],
+            &["--force=yes", "nightly"],
+        ] {
+            assert!(
+                matches!(
+                    parse(args),
+                    Err(ArgsError::MissingValue(_) | ArgsError::UnknownOption(_))
+                ),
+                "{:?}",
+                args
+            );
+        }
+        assert!(matches!(
+            parse(&["--verbose", "nightly"]),
+            Err(ArgsError::UnknownOption(_))
+        ));
+        assert!(matches!(parse(&["-c", "clippy"]), Err(ArgsError::MissingToolchain)));
+    }
+
+    #[test]
+    fn reads_environment() {
+        let host = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let env = |name: &str| match name {
+            "RUSTUP_TOOLCHAIN" => Some("1.70".to_string()),
+            "RUSTUP_DIST_SERVER" => Some("https://mirror.example".to_string()),
+            _ => None,
+        };
+        let args = InstallArgs::parse(["--profile", "minimal"], &host, &env).unwrap();
+        assert_eq!(toolchains(&args), ["1.70-x86_64-unknown-linux-gnu"]);
+        assert_eq!(args.dist_server.as_deref(), Some("https://mirror.example"));
+    }
+}
@@ -0,0 +1,74 @@
+#![allow(clippy::uninlined_format_args)]
+
+use clap::Parser;
+use rustup_toolchain_manifest::fetch::HttpFetcher;
+use rustup_toolchain_manifest::proxy::CachingProxy;
+use rustup_toolchain_manifest::server::{DistServer, DEFAULT_MAX_CONNECTIONS, DEFAULT_TIMEOUT};
+use rustup_toolchain_manifest::ManifestFetcher;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Serves a toolchain mirror directory with the same URL layout as
+/// static.rust-lang.org
+#[derive(Debug, Parser)]
+#[clap(author, version)]
+struct Cli {
+    /// The mirror directory to serve (or the cache directory in proxy mode)
+    root: PathBuf,
+
+    /// The address to listen on
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Act as a caching proxy for the specified distribution server (e.g.
+    /// https://static.rust-lang.org)
+    #[clap(long)]
+    upstream: Option<String>,
+
+    /// In proxy mode, the number of seconds before channel manifests are
+    /// revalidated
+    #[clap(long, default_value_t = 600)]
+    manifest_ttl: u64,
+
+    /// The number of seconds a connection may be idle before it is closed
+    #[clap(long, default_value_t = DEFAULT_TIMEOUT.as_secs())]
+    timeout: u64,
+
+    /// The number of connections to handle at once
+    #[clap(long, default_value_t = DEFAULT_MAX_CONNECTIONS)]
+    max_connections: usize,
+}
+
+fn run(cli: &Cli) -> CliResult<()> {
+    let server = if let Some(upstream) = &cli.upstream {
+        let upstream = ManifestFetcher::with_dist_server(HttpFetcher::new(), upstream);
+        let proxy =
+            CachingProxy::new(cli.root.clone(), upstream).with_manifest_ttl(Duration::from_secs(cli.manifest_ttl));
+        DistServer::bind_with_source(cli.listen.as_str(), proxy)
+    } else {
+        DistServer::bind(cli.listen.as_str(), cli.root.clone())
+    }?
+    .with_timeout(Duration::from_secs(cli.timeout))
+    .with_max_connections(cli.max_connections);
+    let base_url = server.base_url()?;
+    match &cli.upstream {
+        Some(upstream) => println!("Proxying {} via {} at {}", upstream, cli.root.display(), base_url),
+        None => println!("Serving {} at {}", cli.root.display(), base_url),
+    }
+    println!("Use with: RUSTUP_DIST_SERVER={}", base_url);
+    server.run()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(&Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,499 @@
+#![allow(clippy::uninlined_format_args)]
+
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use rustup_toolchain_manifest::diff::ManifestDiff;
+use rustup_toolchain_manifest::fetch::{DirectoryFetcher, HttpFetcher, DEFAULT_DIST_SERVER};
+use rustup_toolchain_manifest::history::ManifestHistory;
+use rustup_toolchain_manifest::host::{detect_host, HostFallbacks};
+use rustup_toolchain_manifest::report::AvailabilityReport;
+use rustup_toolchain_manifest::{
+    lint, ComponentName, InstallPlan, InstallSpec, Manifest, ManifestFetcher, SupportedTarget, Toolchain,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use target_lexicon::Triple;
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Queries Rust toolchain manifests
+#[derive(Debug, Parser)]
+#[clap(author, version)]
+struct Cli {
+    /// Read manifests from a mirror or cache directory rather than a
+    /// distribution server
+    #[clap(long, global = true)]
+    cache: Option<PathBuf>,
+
+    /// The distribution server to fetch manifests from (defaults to
+    /// RUSTUP_DIST_SERVER or the official server)
+    #[clap(long, global = true)]
+    dist_server: Option<String>,
+
+    /// Output JSON rather than a table
+    #[clap(long, global = true)]
+    json: bool,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the installation profiles and their components
+    Profiles {
+        /// A toolchain name or manifest file
+        manifest: String,
+    },
+
+    /// List the components available on a host
+    Components {
+        /// A toolchain name or manifest file
+        manifest: String,
+
+        /// The host triple
+        #[clap(long)]
+        host: Option<String>,
+    },
+
+    /// List the hosts and targets the toolchain is available for
+    Targets {
+        /// A toolchain name or manifest file
+        manifest: String,
+    },
+
+    /// Resolve an install specification to packages
+    Resolve {
+        /// A toolchain name or manifest file
+        manifest: String,
+
+        #[clap(flatten)]
+        spec: SpecArgs,
+    },
+
+    /// Resolve an install specification to the tarballs to download
+    Downloads {
+        /// A toolchain name or manifest file
+        manifest: String,
+
+        #[clap(flatten)]
+        spec: SpecArgs,
+    },
+
+    /// Show the differences between two manifests
+    Diff {
+        /// The older toolchain name or manifest file
+        old: String,
+
+        /// The newer toolchain name or manifest file
+        new: String,
+    },
+
+    /// Check a manifest for internal inconsistencies, failing if any are
+    /// found
+    Lint {
+        /// A toolchain name or manifest file
+        manifest: String,
+    },
+
+    /// Report component availability over recent dates from a directory of
+    /// manifests
+    Report {
+        /// A directory containing manifests for the channel, named
+        /// channel-rust-<channel>.toml as in a mirror
+        directory: PathBuf,
+
+        /// The channel to report on
+        #[clap(long, default_value = "nightly")]
+        channel: String,
+
+        /// A history index file to reuse and update, to avoid parsing every
+        /// manifest again
+        #[clap(long)]
+        index: Option<PathBuf>,
+
+        /// The number of days to report on
+        #[clap(long, default_value = "7")]
+        days: u32,
+
+        /// The targets to report on (defaults to every host)
+        #[clap(short, long = "target", value_delimiter = ',')]
+        targets: Vec<String>,
+
+        /// The components to report on (defaults to every component)
+        #[clap(short, long = "component", value_delimiter = ',')]
+        components: Vec<String>,
+
+        /// Output a static HTML page
+        #[clap(long, conflicts_with = "json")]
+        html: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct SpecArgs {
+    /// The host triple (defaults to the toolchain host or this host)
+    #[clap(long)]
+    host: Option<String>,
+
+    /// The installation profile
+    #[clap(long, default_value = "default")]
+    profile: String,
+
+    /// Additional components to install
+    #[clap(short, long = "component", value_delimiter = ',')]
+    components: Vec<ComponentName>,
+
+    /// Additional targets to install the standard library for
+    #[clap(short, long = "target", value_delimiter = ',')]
+    targets: Vec<Triple>,
+
+    /// Skip components and targets which are unknown or unavailable rather
+    /// than failing
+    #[clap(long)]
+    skip_unavailable: bool,
+
+    /// Use builds for compatible hosts (e.g. x86_64 macOS under Rosetta) for
+    /// packages unavailable for the host
+    #[clap(long, conflicts_with = "skip_unavailable")]
+    host_fallbacks: bool,
+}
+
+impl SpecArgs {
+    fn plan(&self, manifest: &Manifest, host: &Triple) -> CliResult<InstallPlan> {
+        let spec = self.to_install_spec();
+        if !self.skip_unavailable {
+            let issues = spec.validate(manifest, host);
+            if !issues.is_empty() {
+                let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+                return Err(format!("Invalid install specification: {}", issues.join(", ")).into());
+            }
+        }
+        let plan = if self.skip_unavailable {
+            manifest.plan_install_available(host, &spec)?
+        } else if self.host_fallbacks {
+            manifest.plan_install_with_fallbacks(host, &spec, &HostFallbacks::standard())?
+        } else {
+            manifest.plan_install(host, &spec)?
+        };
+        for skipped in plan.skipped() {
+            eprintln!("Note: {}", skipped);
+        }
+        for substituted in plan.substituted() {
+            eprintln!("Note: {}", substituted);
+        }
+        for missing in plan.missing() {
+            eprintln!("Warning: skipped {}", missing);
+        }
+        Ok(plan)
+    }
+
+    fn to_install_spec(&self) -> InstallSpec {
+        let spec = self
+            .components
+            .iter()
+            .cloned()
+            .fold(InstallSpec::new(&self.profile), InstallSpec::with_component);
+        self.targets.iter().cloned().fold(spec, InstallSpec::with_target)
+    }
+}
+
+/// Tabular command output which can also be rendered as JSON objects keyed by
+/// the column headers
+struct Output {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Output {
+    fn new(headers: Vec<&'static str>) -> Output {
+        Output {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    fn push<I: IntoIterator<Item = S>, S: ToString>(&mut self, row: I) {
+        self.rows.push(row.into_iter().map(|s| s.to_string()).collect());
+    }
+
+    fn print(mut self, json: bool) -> CliResult<()> {
+        self.rows.sort();
+        if json {
+            let objects: Vec<serde_json::Map<String, serde_json::Value>> = self
+                .rows
+                .into_iter()
+                .map(|row| {
+                    self.headers
+                        .iter()
+                        .map(|h| h.to_string())
+                        .zip(row.into_iter().map(serde_json::Value::String))
+                        .collect()
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&objects)?);
+        } else {
+            let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+            for row in &self.rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            let print_row = |cells: Vec<&str>| {
+                let line: Vec<String> = cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect();
+                println!("{}", line.join("  ").trim_end());
+            };
+            let headers: Vec<String> = self.headers.iter().map(|h| h.to_uppercase()).collect();
+            print_row(headers.iter().map(String::as_str).collect());
+            for row in &self.rows {
+                print_row(row.iter().map(String::as_str).collect());
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Context {
+    cache: Option<PathBuf>,
+    dist_server: String,
+}
+
+impl Context {
+    /// Loads a manifest from a file if `argument` names one, otherwise
+    /// treats it as a toolchain name and fetches the manifest
+    fn load(&self, argument: &str) -> CliResult<(Option<Toolchain>, Manifest)> {
+        if Path::new(argument).is_file() {
+            let text = std::fs::read_to_string(argument)?;
+            return Ok((None, Manifest::try_from(text.as_str())?));
+        }
+        let toolchain = Toolchain::from_str(argument)?;
+        let manifest = if let Some(cache) = &self.cache {
+            ManifestFetcher::new(DirectoryFetcher::new(cache, DEFAULT_DIST_SERVER)).fetch_manifest(&toolchain)?
+        } else {
+            ManifestFetcher::with_dist_server(HttpFetcher::new(), &self.dist_server).fetch_manifest(&toolchain)?
+        };
+        let manifest = manifest.ok_or_else(|| format!("No manifest found for toolchain {}", toolchain))?;
+        Ok((Some(toolchain), manifest))
+    }
+}
+
+fn resolve_host(host: Option<&str>, toolchain: Option<&Toolchain>) -> CliResult<Triple> {
+    Ok(match (host, toolchain.and_then(|t| t.host.clone())) {
+        (Some(host), _) => Triple::from_str(host)?,
+        (None, Some(host)) => host,
+        (None, None) => detect_host(),
+    })
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let context = Context {
+        cache: cli.cache,
+        dist_server: cli
+            .dist_server
+            .or_else(|| std::env::var("RUSTUP_DIST_SERVER").ok())
+            .unwrap_or_else(|| DEFAULT_DIST_SERVER.to_string()),
+    };
+    let output = match cli.command {
+        Command::Profiles { manifest } => {
+            let (_, manifest) = context.load(&manifest)?;
+            let mut output = Output::new(vec!["profile", "components"]);
+            for profile in manifest.get_profiles() {
+                let components = manifest.get_profile_components(&profile).unwrap_or_default();
+                output.push([profile, components.join(",")]);
+            }
+            output
+        }
+        Command::Components { manifest, host } => {
+            let (toolchain, manifest) = context.load(&manifest)?;
+            let host = resolve_host(host.as_deref(), toolchain.as_ref())?;
+            let mut output = Output::new(vec!["package", "target", "kind"]);
+            for component in manifest.get_components(&host)? {
+                let kind = if component.is_extension {
+                    "extension"
+                } else {
+                    "component"
+                };
+                output.push([component.package, component.target.to_string(), kind.to_string()]);
+            }
+            output
+        }
+        Command::Targets { manifest } => {
+            let (_, manifest) = context.load(&manifest)?;
+            let hosts: Vec<String> = manifest.get_hosts().iter().map(ToString::to_string).collect();
+            let mut output = Output::new(vec!["target", "host"]);
+            for target in manifest.get_targets() {
+                let target = target.to_string();
+                let is_host = hosts.contains(&target);
+                output.push([target, is_host.to_string()]);
+            }
+            output
+        }
+        Command::Resolve { manifest, spec } => {
+            let (toolchain, manifest) = context.load(&manifest)?;
+            let host = resolve_host(spec.host.as_deref(), toolchain.as_ref())?;
+            let mut output = Output::new(vec!["package", "target", "reason"]);
+            for entry in &spec.plan(&manifest, &host)? {
+                let reasons: Vec<String> = entry.resolutions.iter().map(ToString::to_string).collect();
+                output.push([entry.package.clone(), entry.target.to_string(), reasons.join(", ")]);
+            }
+            output
+        }
+        Command::Downloads { manifest, spec } => {
+            let (toolchain, manifest) = context.load(&manifest)?;
+            let host = resolve_host(spec.host.as_deref(), toolchain.as_ref())?;
+            let mut output = Output::new(vec!["package", "target", "compression", "url", "sha256"]);
+            for package in spec.plan(&manifest, &host)?.to_downloads(&manifest)? {
+                for (compression, binary) in &package.tarballs {
+                    let digests: Vec<String> = binary.digests.values().map(ToString::to_string).collect();
+                    output.push([
+                        package.name.clone(),
+                        package.supported_target.to_string(),
+                        format!("{:?}", compression),
+                        binary.url.clone(),
+                        digests.join(","),
+                    ]);
+                }
+            }
+            output
+        }
+        Command::Diff { old, new } => {
+            let (_, old) = context.load(&old)?;
+            let (_, new) = context.load(&new)?;
+            let diff = ManifestDiff::new(&old, &new);
+            let mut output = Output::new(vec!["change", "item", "target", "old", "new"]);
+            let none = String::new;
+            for package in diff.added_packages {
+                output.push(["added-package".to_string(), package, none(), none(), none()]);
+            }
+            for package in diff.removed_packages {
+                output.push(["removed-package".to_string(), package, none(), none(), none()]);
+            }
+            for change in diff.version_changes {
+                output.push([
+                    "version".to_string(),
+                    change.package,
+                    none(),
+                    change.old.unwrap_or_default(),
+                    change.new.unwrap_or_default(),
+                ]);
+            }
+            for change in diff.availability_changes {
+                output.push([
+                    "availability".to_string(),
+                    change.package,
+                    change.target.to_string(),
+                    format!("{:?}", change.old),
+                    format!("{:?}", change.new),
+                ]);
+            }
+            for change in diff.profile_changes {
+                output.push([
+                    "profile".to_string(),
+                    change.profile,
+                    none(),
+                    change.removed.join(","),
+                    change.added.join(","),
+                ]);
+            }
+            for (from, to) in diff.added_renames {
+                output.push(["added-rename".to_string(), from, none(), none(), to]);
+            }
+            for (from, to) in diff.removed_renames {
+                output.push(["removed-rename".to_string(), from, none(), to, none()]);
+            }
+            output
+        }
+        Command::Lint { manifest } => {
+            let (_, manifest) = context.load(&manifest)?;
+            let issues = lint::lint(&manifest);
+            let count = issues.len();
+            let mut output = Output::new(vec!["issue"]);
+            for issue in issues {
+                output.push([issue]);
+            }
+            output.print(cli.json)?;
+            if count > 0 {
+                return Err(format!("Found {} issue(s) in manifest", count).into());
+            }
+            return Ok(());
+        }
+        Command::Report {
+            directory,
+            channel,
+            index,
+            days,
+            targets,
+            components,
+            html,
+        } => {
+            let mut history = match &index {
+                Some(index) if index.is_file() => ManifestHistory::load(index)?,
+                _ => ManifestHistory::new(&channel),
+            };
+            if let Some(index) = index.as_ref().filter(|_| history.channel() != channel) {
+                return Err(format!("Index {} is for channel {}", index.display(), history.channel()).into());
+            }
+            history.update(&directory)?;
+            if let Some(index) = &index {
+                history.save(index)?;
+            }
+            let targets = targets
+                .iter()
+                .map(|target| SupportedTarget::from_str(target))
+                .collect::<Result<Vec<_>, _>>()?;
+            let report = AvailabilityReport::new(&history, days, &targets, &components);
+            if html {
+                print!("{}", report.to_html());
+                return Ok(());
+            }
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            let mut output = Output::new(vec!["target", "component", "recent", "last-available"]);
+            let date = |date: Option<NaiveDate>| date.map(|d| d.to_string()).unwrap_or_default();
+            for target in report.targets {
+                output.push([
+                    target.target.clone(),
+                    "(all)".to_string(),
+                    String::new(),
+                    date(target.last_complete),
+                ]);
+                for component in target.components {
+                    let recent: String = component
+                        .available
+                        .iter()
+                        .map(|available| match available {
+                            Some(true) => '+',
+                            Some(false) => '-',
+                            None => '?',
+                        })
+                        .collect();
+                    output.push([
+                        target.target.clone(),
+                        component.component,
+                        recent,
+                        date(component.last_available),
+                    ]);
+                }
+            }
+            output
+        }
+    };
+    output.print(cli.json)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
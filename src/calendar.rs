@@ -0,0 +1,266 @@
+use crate::toolchain::{Channel, ReleaseLookup};
+use crate::Manifest;
+use chrono::{Days, NaiveDate};
+use std::collections::BTreeMap;
+
+/// The number of days between stable releases
+pub const RELEASE_CYCLE_DAYS: u16 = 42;
+
+/// Release dates of early versions, after which releases have followed the
+/// six-week train without exception
+const ANCHORS: [(u16, (i32, u32, u32)); 4] = [
+    (0, (2015, 5, 15)),
+    (1, (2015, 6, 25)),
+    (2, (2015, 8, 7)),
+    (3, (2015, 9, 17)),
+];
+
+/// A calendar of Rust 1.x releases, relating versions to the dates on which
+/// they were (or are expected to be) released, and dates to the versions the
+/// stable, beta and nightly channels were on.
+///
+/// Release dates are extrapolated from the closest earlier anchor release
+/// along the six-week train, so work for future versions too. Versions and
+/// dates observed in manifests take precedence over the calculated ones. The
+/// beta and nightly channels are assumed to move to the next version on the
+/// day of each stable release; in practice nightlies change version a few
+/// days earlier, which observed manifests will correct for.
+#[derive(Clone, Debug)]
+pub struct ReleaseCalendar {
+    anchors: BTreeMap<u16, NaiveDate>,
+    stable: BTreeMap<NaiveDate, Channel>,
+    beta: BTreeMap<NaiveDate, Channel>,
+    nightly: BTreeMap<NaiveDate, Channel>,
+}
+
+impl Default for ReleaseCalendar {
+    fn default() -> ReleaseCalendar {
+        ReleaseCalendar::new()
+    }
+}
+
+impl ReleaseCalendar {
+    /// Constructs a calendar calibrated from the built-in anchor releases
+    #[must_use]
+    pub fn new() -> ReleaseCalendar {
+        let anchors = ANCHORS
+            .iter()
+            .filter_map(|(minor, (year, month, day))| Some((*minor, NaiveDate::from_ymd_opt(*year, *month, *day)?)))
+            .collect();
+        ReleaseCalendar {
+            anchors,
+            stable: BTreeMap::new(),
+            beta: BTreeMap::new(),
+            nightly: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a known release date for version `1.<minor>.0`, which later
+    /// versions will be calculated from
+    #[must_use]
+    pub fn with_anchor(mut self, minor: u16, date: NaiveDate) -> ReleaseCalendar {
+        self.anchors.insert(minor, date);
+        self
+    }
+
+    /// Records the version of the `rust` package in a manifest as the version
+    /// its channel was on at the manifest date. The manifests of `.0` stable
+    /// releases also become anchors. Manifests without a parseable version are
+    /// ignored.
+    pub fn add_manifest(&mut self, manifest: &Manifest) {
+        let Some(channel) = manifest.get_release() else {
+            return;
+        };
+        let date = manifest.get_date();
+        match channel {
+            Channel::Version(1, minor, Some(0)) => {
+                self.anchors.insert(minor, date);
+                self.stable.insert(date, channel);
+            }
+            Channel::Version(..) => {
+                self.stable.insert(date, channel);
+            }
+            Channel::VersionBeta { .. } => {
+                self.beta.insert(date, channel);
+            }
+            Channel::VersionNightly { .. } => {
+                self.nightly.insert(date, channel);
+            }
+            _ => {}
+        }
+    }
+
+    /// The date on which a stable version was, or is expected to be,
+    /// released. Patch releases other than `.0` cannot be predicted, so only
+    /// have a date if observed in a manifest. Returns `None` for channels
+    /// other than `Channel::Version` and for versions other than 1.x.
+    #[must_use]
+    pub fn release_date(&self, channel: &Channel) -> Option<NaiveDate> {
+        match channel {
+            Channel::Version(1, minor, None | Some(0)) => {
+                let (anchor_minor, anchor_date) = self.anchors.range(..=minor).next_back()?;
+                let cycles = u64::from(minor - anchor_minor);
+                anchor_date.checked_add_days(Days::new(cycles * u64::from(RELEASE_CYCLE_DAYS)))
+            }
+            Channel::Version(1, ..) => self
+                .stable
+                .iter()
+                .find(|(_, observed)| *observed == channel)
+                .map(|(date, _)| *date),
+            _ => None,
+        }
+    }
+
+    /// The minor version of the latest stable release on or before a date,
+    /// according to the anchors
+    fn stable_minor(&self, date: NaiveDate) -> Option<u16> {
+        let (anchor_minor, anchor_date) = self.anchors.iter().rev().find(|(_, anchor)| **anchor <= date)?;
+        let cycles = u16::try_from((date - *anchor_date).num_days() / i64::from(RELEASE_CYCLE_DAYS)).ok()?;
+        let mut minor = anchor_minor.checked_add(cycles)?;
+        // An anchor later than the extrapolated release takes precedence
+        if let Some((next_minor, _)) = self.anchors.range(anchor_minor..).nth(1) {
+            minor = minor.min(next_minor - 1);
+        }
+        Some(minor)
+    }
+
+    /// The stable release current on a date. The patch version is only known
+    /// if a release of that minor version was observed in a manifest by that
+    /// date. Returns `None` for dates before Rust 1.0.
+    #[must_use]
+    pub fn stable_on(&self, date: NaiveDate) -> Option<Channel> {
+        let minor = self.stable_minor(date)?;
+        let observed = self.stable.range(..=date).next_back().map(|(_, channel)| channel);
+        match observed {
+            Some(Channel::Version(1, observed_minor, _)) if *observed_minor >= minor => observed.cloned(),
+            _ => Some(Channel::Version(1, minor, None)),
+        }
+    }
+
+    /// The version the beta channel was on at a date
+    #[must_use]
+    pub fn beta_on(&self, date: NaiveDate) -> Option<Channel> {
+        observed_between(&self.beta, date).or_else(|| {
+            Some(Channel::VersionBeta {
+                major: 1,
+                minor: self.stable_minor(date)?.checked_add(1)?,
+                patch: None,
+                number: None,
+            })
+        })
+    }
+
+    /// The version the nightly channel was on at a date
+    #[must_use]
+    pub fn nightly_on(&self, date: NaiveDate) -> Option<Channel> {
+        observed_between(&self.nightly, date).or_else(|| {
+            Some(Channel::VersionNightly {
+                major: 1,
+                minor: self.stable_minor(date)?.checked_add(2)?,
+                patch: None,
+            })
+        })
+    }
+}
+
+/// Returns the version observed on a date, or the version observed on both the
+/// closest earlier and later dates if they agree. Beta release numbers are
+/// only retained for exact matches.
+fn observed_between(observed: &BTreeMap<NaiveDate, Channel>, date: NaiveDate) -> Option<Channel> {
+    if let Some(channel) = observed.get(&date) {
+        return Some(channel.clone());
+    }
+    let strip = |channel: &Channel| match channel {
+        Channel::VersionBeta {
+            major, minor, patch, ..
+        } => Channel::VersionBeta {
+            major: *major,
+            minor: *minor,
+            patch: *patch,
+            number: None,
+        },
+        other => other.clone(),
+    };
+    let before = strip(observed.range(..date).next_back()?.1);
+    let after = strip(observed.range(date..).next()?.1);
+    (before == after).then_some(before)
+}
+
+impl ReleaseLookup for ReleaseCalendar {
+    fn resolve(&self, channel: &Channel, date: NaiveDate) -> Option<Channel> {
+        match channel {
+            Channel::Stable => self.stable_on(date),
+            Channel::Beta => self.beta_on(date),
+            Channel::Nightly => self.nightly_on(date),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::manifest_text;
+    use std::str::FromStr;
+
+    fn date(string: &str) -> NaiveDate {
+        NaiveDate::from_str(string).unwrap()
+    }
+
+    #[test]
+    fn extrapolates_release_dates() {
+        let calendar = ReleaseCalendar::new();
+        let release_date = |version| calendar.release_date(&Channel::from_str(version).unwrap());
+        assert_eq!(release_date("1.0"), Some(date("2015-05-15")));
+        assert_eq!(release_date("1.69"), Some(date("2023-04-20")));
+        assert_eq!(release_date("1.80.0"), Some(date("2024-07-25")));
+        assert_eq!(release_date("1.80.1"), None);
+        assert_eq!(release_date("nightly"), None);
+    }
+
+    #[test]
+    fn resolves_channels_on_dates() {
+        let calendar = ReleaseCalendar::new();
+        assert_eq!(
+            calendar.stable_on(date("2023-04-19")),
+            Some(Channel::Version(1, 68, None))
+        );
+        assert_eq!(
+            calendar.stable_on(date("2023-04-20")),
+            Some(Channel::Version(1, 69, None))
+        );
+        assert_eq!(
+            calendar.nightly_on(date("2023-04-20")),
+            Channel::from_str("1.71-nightly").ok()
+        );
+        assert_eq!(calendar.stable_on(date("2015-05-14")), None);
+    }
+
+    #[test]
+    fn observed_manifests_take_precedence() {
+        let mut calendar = ReleaseCalendar::new();
+        let host = "x86_64-unknown-linux-gnu";
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        // A release a day later than extrapolated moves later releases too
+        let text = manifest_text("2023-04-21", "1.69.0 (84c898d65 2023-04-16)", commit, host, &[]);
+        calendar.add_manifest(&Manifest::try_from(text.as_str()).unwrap());
+        let text = manifest_text("2023-05-01", "1.69.1 (84c898d65 2023-04-30)", commit, host, &[]);
+        calendar.add_manifest(&Manifest::try_from(text.as_str()).unwrap());
+        assert_eq!(
+            calendar.release_date(&Channel::Version(1, 70, None)),
+            Some(date("2023-06-02"))
+        );
+        assert_eq!(
+            calendar.release_date(&Channel::Version(1, 69, Some(1))),
+            Some(date("2023-05-01"))
+        );
+        assert_eq!(
+            calendar.stable_on(date("2023-04-20")),
+            Some(Channel::Version(1, 68, None))
+        );
+        assert_eq!(
+            calendar.stable_on(date("2023-05-02")),
+            Some(Channel::Version(1, 69, Some(1)))
+        );
+    }
+}
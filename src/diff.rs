@@ -0,0 +1,288 @@
+use crate::{Manifest, SupportedTarget};
+use chrono::NaiveDate;
+use std::collections::{BTreeSet, HashMap};
+
+/// Whether a package build is present in a manifest
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Availability {
+    /// The package was not listed for the target
+    Unknown,
+
+    /// The package was listed for the target but marked unavailable
+    Unavailable,
+
+    /// The package was available for the target
+    Available,
+}
+
+/// A change in the version of a package between two manifests
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionChange {
+    /// The package name
+    pub package: String,
+
+    /// The version in the older manifest
+    pub old: Option<String>,
+
+    /// The version in the newer manifest
+    pub new: Option<String>,
+}
+
+/// A change in the availability of a package for a target between two
+/// manifests
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AvailabilityChange {
+    /// The package name
+    pub package: String,
+
+    /// The target the package is built for
+    pub target: SupportedTarget,
+
+    /// The availability in the older manifest
+    pub old: Availability,
+
+    /// The availability in the newer manifest
+    pub new: Availability,
+}
+
+/// A change in the components of a profile between two manifests
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProfileChange {
+    /// The profile name
+    pub profile: String,
+
+    /// Components present only in the newer manifest
+    pub added: Vec<String>,
+
+    /// Components present only in the older manifest
+    pub removed: Vec<String>,
+}
+
+/// The differences between two manifests. All lists are sorted.
+#[derive(Clone, Debug)]
+pub struct ManifestDiff {
+    /// The date of the older manifest
+    pub old_date: NaiveDate,
+
+    /// The date of the newer manifest
+    pub new_date: NaiveDate,
+
+    /// Packages present only in the newer manifest
+    pub added_packages: Vec<String>,
+
+    /// Packages present only in the older manifest
+    pub removed_packages: Vec<String>,
+
+    /// Packages present in both manifests whose version differs
+    pub version_changes: Vec<VersionChange>,
+
+    /// Package builds whose availability differs
+    pub availability_changes: Vec<AvailabilityChange>,
+
+    /// Profiles whose components differ
+    pub profile_changes: Vec<ProfileChange>,
+
+    /// Renames (from, to) present only in the newer manifest
+    pub added_renames: Vec<(String, String)>,
+
+    /// Renames (from, to) present only in the older manifest
+    pub removed_renames: Vec<(String, String)>,
+}
+
+impl ManifestDiff {
+    /// Computes the differences between an older and a newer manifest
+    #[must_use]
+    pub fn new(old: &Manifest, new: &Manifest) -> ManifestDiff {
+        let old_packages: BTreeSet<String> = old.get_package_names().into_iter().collect();
+        let new_packages: BTreeSet<String> = new.get_package_names().into_iter().collect();
+        let version =
+            |manifest: &Manifest, package: &str| manifest.get_package_info(package).map(|i| i.version.clone());
+        let version_changes = old_packages
+            .intersection(&new_packages)
+            .filter_map(|package| {
+                let (old, new) = (version(old, package), version(new, package));
+                (old != new).then(|| VersionChange {
+                    package: package.clone(),
+                    old,
+                    new,
+                })
+            })
+            .collect();
+
+        let mut availability_changes = Vec::new();
+        for package in old_packages.union(&new_packages) {
+            let old_availability = Self::availability(old, package);
+            let new_availability = Self::availability(new, package);
+            let mut targets: Vec<&SupportedTarget> = old_availability.keys().chain(new_availability.keys()).collect();
+            targets.sort_by_key(ToString::to_string);
+            targets.dedup();
+            for target in targets {
+                let old = old_availability.get(target).copied().unwrap_or(Availability::Unknown);
+                let new = new_availability.get(target).copied().unwrap_or(Availability::Unknown);
+                if old != new {
+                    availability_changes.push(AvailabilityChange {
+                        package: package.clone(),
+                        target: target.clone(),
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+
+        let profiles: BTreeSet<String> = old.get_profiles().into_iter().chain(new.get_profiles()).collect();
+        let profile_changes = profiles
+            .into_iter()
+            .filter_map(|profile| {
+                let components = |manifest: &Manifest| -> BTreeSet<String> {
+                    manifest
+                        .get_profile_components(&profile)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect()
+                };
+                let (old, new) = (components(old), components(new));
+                let added: Vec<String> = new.difference(&old).cloned().collect();
+                let removed: Vec<String> = old.difference(&new).cloned().collect();
+                (!added.is_empty() || !removed.is_empty()).then_some(ProfileChange {
+                    profile,
+                    added,
+                    removed,
+                })
+            })
+            .collect();
+
+        let renames = |manifest: &Manifest| -> BTreeSet<(String, String)> {
+            manifest
+                .get_renames()
+                .iter()
+                .map(|(from, to)| (from.clone(), to.clone()))
+                .collect()
+        };
+        let (old_renames, new_renames) = (renames(old), renames(new));
+
+        ManifestDiff {
+            old_date: old.get_date(),
+            new_date: new.get_date(),
+            added_packages: new_packages.difference(&old_packages).cloned().collect(),
+            removed_packages: old_packages.difference(&new_packages).cloned().collect(),
+            version_changes,
+            availability_changes,
+            profile_changes,
+            added_renames: new_renames.difference(&old_renames).cloned().collect(),
+            removed_renames: old_renames.difference(&new_renames).cloned().collect(),
+        }
+    }
+
+    fn availability(manifest: &Manifest, package: &str) -> HashMap<SupportedTarget, Availability> {
+        manifest
+            .get_package_availability(package)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(target, available)| {
+                let availability = if available {
+                    Availability::Available
+                } else {
+                    Availability::Unavailable
+                };
+                (target, availability)
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the manifests did not differ (other than in date)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added_packages.is_empty()
+            && self.removed_packages.is_empty()
+            && self.version_changes.is_empty()
+            && self.availability_changes.is_empty()
+            && self.profile_changes.is_empty()
+            && self.added_renames.is_empty()
+            && self.removed_renames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::manifest_text;
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    fn manifest(date: &str, version: &str, packages: &[(&str, bool)], renames: &str) -> Manifest {
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let text = manifest_text(date, version, commit, HOST, packages)
+            .replace("[renames]\n", &format!("[renames]\n{}", renames));
+        Manifest::try_from(text.as_str()).unwrap()
+    }
+
+    #[test]
+    fn identical_manifests_do_not_differ() {
+        let old = manifest("2023-06-01", "1.70.0", &[("clippy", true)], "");
+        let new = manifest("2023-06-02", "1.70.0", &[("clippy", true)], "");
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(diff.is_empty());
+        assert_eq!(diff.old_date, NaiveDate::from_ymd_opt(2023, 6, 1).unwrap());
+        assert_eq!(diff.new_date, NaiveDate::from_ymd_opt(2023, 6, 2).unwrap());
+    }
+
+    #[test]
+    fn reports_changes() {
+        let old = manifest(
+            "2023-06-01",
+            "1.70.0",
+            &[("clippy", true), ("rustfmt", true)],
+            "rls = { to = \"rls-preview\" }\n",
+        );
+        let new = manifest(
+            "2023-06-02",
+            "1.71.0",
+            &[("clippy", false), ("miri", true)],
+            "clippy-preview = { to = \"clippy\" }\n",
+        );
+        let diff = ManifestDiff::new(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.added_packages, ["miri"]);
+        assert_eq!(diff.removed_packages, ["rustfmt"]);
+
+        let version_change = |package: &str| VersionChange {
+            package: package.to_string(),
+            old: Some("1.70.0".to_string()),
+            new: Some("1.71.0".to_string()),
+        };
+        assert_eq!(diff.version_changes, [version_change("clippy"), version_change("rust")]);
+
+        let target = SupportedTarget::Dependent(Triple::from_str(HOST).unwrap());
+        let availability_change = |package: &str, old, new| AvailabilityChange {
+            package: package.to_string(),
+            target: target.clone(),
+            old,
+            new,
+        };
+        assert_eq!(
+            diff.availability_changes,
+            [
+                availability_change("clippy", Availability::Available, Availability::Unavailable),
+                availability_change("miri", Availability::Unknown, Availability::Available),
+                availability_change("rustfmt", Availability::Available, Availability::Unknown),
+            ]
+        );
+
+        assert_eq!(
+            diff.profile_changes,
+            [ProfileChange {
+                profile: "default".to_string(),
+                added: vec!["miri".to_string()],
+                removed: vec!["rustfmt".to_string()],
+            }]
+        );
+        assert_eq!(
+            diff.added_renames,
+            [("clippy-preview".to_string(), "clippy".to_string())]
+        );
+        assert_eq!(diff.removed_renames, [("rls".to_string(), "rls-preview".to_string())]);
+    }
+}
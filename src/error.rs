@@ -8,6 +8,10 @@ pub enum Error {
     #[error("TOML deserialization error: {0}")]
     TomlDeserialize(#[from] basic_toml::Error),
 
+    /// A value could not be serialized as TOML
+    #[error("TOML serialization error: {0}")]
+    TomlSerialize(basic_toml::Error),
+
     /// The manifest did not conform to the expected structure (parsing was fine
     /// though)
     #[error("Manifest had incorect structure: {0}")]
@@ -50,4 +54,36 @@ pub enum Error {
     /// The package's version information was missing
     #[error("Missing versioning information for package {0}")]
     MissingPackageVersion(String),
+
+    /// An I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A remote file could not be retrieved
+    #[error("Failed to fetch {0}: {1}")]
+    Fetch(String, String),
+
+    /// A remote file did not exist
+    #[error("Remote file not found: {0}")]
+    NotFound(String),
+
+    /// A downloaded file did not match its expected digest
+    #[error("Digest mismatch for {0}")]
+    DigestMismatch(String),
+
+    /// A manifest was not valid UTF-8
+    #[error("Manifest at {0} was not valid UTF-8")]
+    ManifestNotUtf8(String),
+
+    /// A URL did not refer to a location on the expected distribution server
+    #[error("URL {0} is not located on distribution server {1}")]
+    UnexpectedServer(String, String),
+
+    /// A `Cargo.toml` could not be read or contained invalid values
+    #[error("Invalid Cargo manifest {0}: {1}")]
+    CargoManifest(String, String),
+
+    /// A commit was not present in the supplied commit history
+    #[error("Commit {0} not found in commit history")]
+    CommitNotInHistory(String),
 }
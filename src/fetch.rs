@@ -0,0 +1,368 @@
+use crate::manifest::RemoteBinary;
+use crate::mirror::matches_digest_file;
+use crate::toolchain::Channel;
+use crate::{Error, Manifest, Toolchain};
+use std::path::{Component, Path, PathBuf};
+
+/// The server from which official Rust toolchains are distributed
+pub const DEFAULT_DIST_SERVER: &str = "https://static.rust-lang.org";
+
+/// A source of remote files
+pub trait Fetcher {
+    /// Fetches the file at the specified URL. Returns `None` if the file does
+    /// not exist.
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error>;
+}
+
+impl<F: Fetcher + ?Sized> Fetcher for &F {
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        (**self).fetch(url)
+    }
+}
+
+impl<F: Fetcher + ?Sized> Fetcher for Box<F> {
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        (**self).fetch(url)
+    }
+}
+
+/// Returns the path of `url` relative to the root of `dist_server`
+pub fn dist_path<'a>(url: &'a str, dist_server: &str) -> Result<&'a str, Error> {
+    let dist_server = dist_server.trim_end_matches('/');
+    url.strip_prefix(dist_server)
+        .and_then(|path| path.strip_prefix('/'))
+        .ok_or_else(|| Error::UnexpectedServer(url.to_string(), dist_server.to_string()))
+}
+
+/// Converts a path relative to the root of a distribution server to a
+/// filesystem path under `root`, rejecting paths which would escape it
+pub(crate) fn local_path(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(root.join(relative))
+    } else {
+        None
+    }
+}
+
+/// Fetches toolchain manifests and the binaries they refer to from a
+/// distribution server
+#[derive(Clone, Debug)]
+pub struct ManifestFetcher<F> {
+    fetcher: F,
+    dist_server: String,
+}
+
+impl<F: Fetcher> ManifestFetcher<F> {
+    /// Constructs a manifest fetcher that retrieves files from the official
+    /// distribution server
+    pub fn new(fetcher: F) -> ManifestFetcher<F> {
+        Self::with_dist_server(fetcher, DEFAULT_DIST_SERVER)
+    }
+
+    /// Constructs a manifest fetcher that retrieves files from the specified
+    /// distribution server
+    pub fn with_dist_server(fetcher: F, dist_server: &str) -> ManifestFetcher<F> {
+        ManifestFetcher {
+            fetcher,
+            dist_server: dist_server.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The distribution server files are retrieved from
+    #[must_use]
+    pub fn dist_server(&self) -> &str {
+        self.dist_server.as_str()
+    }
+
+    /// The underlying fetcher
+    #[must_use]
+    pub fn fetcher(&self) -> &F {
+        &self.fetcher
+    }
+
+    /// Returns the URL of a path relative to the root of the distribution
+    /// server
+    #[must_use]
+    pub fn url_for_path(&self, path: &str) -> String {
+        format!("{}/{}", self.dist_server, path.trim_start_matches('/'))
+    }
+
+    /// Fetches the unparsed manifest for a toolchain. Returns `None` if the
+    /// manifest does not exist.
+    pub fn fetch_manifest_text(&self, toolchain: &Toolchain) -> Result<Option<String>, Error> {
+        let url = toolchain.manifest_url_for_server(&self.dist_server);
+        match self.fetcher.fetch(&url)? {
+            Some(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| Error::ManifestNotUtf8(url)),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetches the unparsed manifest for a toolchain and checks it against the
+    /// `.sha256` file published alongside it. Returns `None` if the manifest
+    /// does not exist.
+    pub fn fetch_verified_manifest_text(&self, toolchain: &Toolchain) -> Result<Option<String>, Error> {
+        let Some(text) = self.fetch_manifest_text(toolchain)? else {
+            return Ok(None);
+        };
+        let url = toolchain.manifest_url_for_server(&self.dist_server);
+        let digest_url = format!("{}.sha256", url);
+        let digest = self.fetcher.fetch(&digest_url)?.ok_or(Error::NotFound(digest_url))?;
+        if matches_digest_file(&String::from_utf8_lossy(&digest), text.as_bytes()) {
+            Ok(Some(text))
+        } else {
+            Err(Error::DigestMismatch(url))
+        }
+    }
+
+    /// Fetches and parses the manifest for a toolchain. Returns `None` if the
+    /// manifest does not exist.
+    pub fn fetch_manifest(&self, toolchain: &Toolchain) -> Result<Option<Manifest>, Error> {
+        match self.fetch_manifest_text(toolchain)? {
+            Some(text) => Ok(Some(Manifest::try_from(text.as_str())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves a toolchain to the release its manifest is for, such as
+    /// `1.70.0` for `1.70` or `stable`. Returns `None` if the manifest does not
+    /// exist.
+    pub fn resolve_release(&self, toolchain: &Toolchain) -> Result<Option<Channel>, Error> {
+        let Some(manifest) = self.fetch_manifest(toolchain)? else {
+            return Ok(None);
+        };
+        let release = manifest
+            .get_release()
+            .ok_or_else(|| Error::MissingPackageVersion("rust".to_string()))?;
+        Ok(Some(release))
+    }
+
+    /// Lists the patch releases of a minor version, from `.0` up to the latest
+    /// (which is found by resolving the partial version). Returns an empty
+    /// list if the version has not been released.
+    pub fn patch_releases(&self, major: u16, minor: u16) -> Result<Vec<Channel>, Error> {
+        let toolchain = Toolchain {
+            channel: Channel::Version(major, minor, None),
+            date: None,
+            host: None,
+        };
+        match self.resolve_release(&toolchain)? {
+            Some(Channel::Version(_, _, Some(latest))) => Ok((0..=latest)
+                .map(|patch| Channel::Version(major, minor, Some(patch)))
+                .collect()),
+            Some(release) => Err(Error::IncorrectManifestStructure(format!(
+                "Manifest for {} is for release {}",
+                toolchain, release
+            ))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the URL a binary should be retrieved from. Binaries listed in
+    /// manifests refer to the official distribution server, so this is
+    /// rewritten to refer to the configured server, as Rustup does.
+    #[must_use]
+    pub fn binary_url(&self, binary: &RemoteBinary) -> String {
+        match dist_path(&binary.url, DEFAULT_DIST_SERVER) {
+            Ok(path) => self.url_for_path(path),
+            Err(_) => binary.url.clone(),
+        }
+    }
+
+    /// Fetches a binary and checks it against its digests
+    pub fn fetch_binary(&self, binary: &RemoteBinary) -> Result<Vec<u8>, Error> {
+        let url = self.binary_url(binary);
+        let data = self.fetcher.fetch(&url)?.ok_or_else(|| Error::NotFound(url.clone()))?;
+        if binary.verify(&data) {
+            Ok(data)
+        } else {
+            Err(Error::DigestMismatch(url))
+        }
+    }
+}
+
+/// Fetches files from a local directory laid out like a distribution server
+#[derive(Clone, Debug)]
+pub struct DirectoryFetcher {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl DirectoryFetcher {
+    /// Constructs a fetcher which maps URLs beneath `base_url` to files beneath
+    /// `root`
+    pub fn new<P: Into<PathBuf>>(root: P, base_url: &str) -> DirectoryFetcher {
+        DirectoryFetcher {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl Fetcher for DirectoryFetcher {
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        let relative = dist_path(url, &self.base_url)?;
+        let Some(path) = local_path(&self.root, relative) else {
+            return Ok(None);
+        };
+        match std::fs::read(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Fetches files over HTTP(S)
+#[cfg(feature = "http")]
+#[derive(Clone, Debug, Default)]
+pub struct HttpFetcher {
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "http")]
+impl HttpFetcher {
+    /// Constructs a HTTP fetcher using a default client
+    #[must_use]
+    pub fn new() -> HttpFetcher {
+        HttpFetcher::default()
+    }
+
+    /// Constructs a HTTP fetcher using the supplied client
+    #[must_use]
+    pub fn with_client(client: reqwest::blocking::Client) -> HttpFetcher {
+        HttpFetcher { client }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        let to_error = |e: reqwest::Error| Error::Fetch(url.to_string(), e.to_string());
+        let response = self.client.get(url).send().map_err(to_error)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(to_error)?;
+        let bytes = response.bytes().map_err(to_error)?;
+        Ok(Some(bytes.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{manifest_text, publish, publish_manifest, temp_dir, TARBALL};
+    use std::str::FromStr;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+    const MIRROR: &str = "https://mirror.example/rust";
+
+    fn publish_release(root: &Path, channel: &str, date: &str, version: &str) {
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let text = manifest_text(date, version, commit, HOST, &[("clippy", true)]);
+        publish_manifest(root, channel, &text, true);
+    }
+
+    fn toolchain(name: &str) -> Toolchain {
+        Toolchain::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn verifies_manifest_digests() {
+        let root = temp_dir("fetch-verify");
+        publish_release(&root, "stable", "2023-06-01", "1.70.0 (90c541806 2023-05-31)");
+        let fetcher = ManifestFetcher::with_dist_server(DirectoryFetcher::new(&root, MIRROR), MIRROR);
+        let text = fetcher
+            .fetch_verified_manifest_text(&toolchain("stable"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Manifest::try_from(text.as_str()).unwrap().get_date().to_string(),
+            "2023-06-01"
+        );
+        assert!(fetcher
+            .fetch_verified_manifest_text(&toolchain("beta"))
+            .unwrap()
+            .is_none());
+
+        std::fs::write(root.join("dist/channel-rust-stable.toml"), b"tampered").unwrap();
+        assert!(matches!(
+            fetcher.fetch_verified_manifest_text(&toolchain("stable")),
+            Err(Error::DigestMismatch(url)) if url == format!("{}/dist/channel-rust-stable.toml", MIRROR)
+        ));
+        std::fs::remove_file(root.join("dist/channel-rust-stable.toml.sha256")).unwrap();
+        assert!(matches!(
+            fetcher.fetch_verified_manifest_text(&toolchain("stable")),
+            Err(Error::NotFound(url)) if url.ends_with("channel-rust-stable.toml.sha256")
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rewrites_binary_urls_for_dist_server() {
+        let root = temp_dir("fetch-binary");
+        publish_release(&root, "stable", "2023-06-01", "1.70.0 (90c541806 2023-05-31)");
+        let fetcher = ManifestFetcher::with_dist_server(DirectoryFetcher::new(&root, MIRROR), &format!("{}/", MIRROR));
+        assert_eq!(fetcher.dist_server(), MIRROR);
+        let manifest = fetcher.fetch_manifest(&toolchain("stable")).unwrap().unwrap();
+        let packages = manifest.find_all_downloads().unwrap();
+        let (_, binary) = &packages[0].tarballs[0];
+        assert!(binary.url.starts_with(DEFAULT_DIST_SERVER));
+        let expected = binary.url.replace(DEFAULT_DIST_SERVER, MIRROR);
+        assert_eq!(fetcher.binary_url(binary), expected);
+        assert_eq!(fetcher.fetch_binary(binary).unwrap(), TARBALL);
+
+        let path = dist_path(&expected, MIRROR).unwrap();
+        std::fs::write(root.join(path), b"corrupt").unwrap();
+        assert!(matches!(fetcher.fetch_binary(binary), Err(Error::DigestMismatch(url)) if url == expected));
+        std::fs::remove_file(root.join(path)).unwrap();
+        assert!(matches!(fetcher.fetch_binary(binary), Err(Error::NotFound(url)) if url == expected));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn directory_fetcher_stays_within_root() {
+        let root = temp_dir("fetch-directory");
+        publish(&root, "dist/file.txt", b"contents");
+        let fetcher = DirectoryFetcher::new(&root, MIRROR);
+        assert_eq!(
+            fetcher.fetch(&format!("{}/dist/file.txt", MIRROR)).unwrap().as_deref(),
+            Some(&b"contents"[..])
+        );
+        assert_eq!(fetcher.fetch(&format!("{}/dist/absent.txt", MIRROR)).unwrap(), None);
+        assert_eq!(
+            fetcher.fetch(&format!("{}/dist/../../etc/passwd", MIRROR)).unwrap(),
+            None
+        );
+        assert!(matches!(
+            fetcher.fetch("https://elsewhere.example/dist/file.txt"),
+            Err(Error::UnexpectedServer(..))
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn resolves_partial_versions_to_patch_releases() {
+        let root = temp_dir("fetch-releases");
+        publish_release(&root, "1.70", "2023-07-13", "1.70.1 (a2b1646c5 2023-07-13)");
+        publish_release(&root, "1.71", "2023-07-13", "1.71.0 (8ede3aae2 2023-07-12)");
+        let fetcher = ManifestFetcher::new(DirectoryFetcher::new(&root, DEFAULT_DIST_SERVER));
+        assert_eq!(
+            fetcher.resolve_release(&toolchain("1.70")).unwrap(),
+            Some(Channel::Version(1, 70, Some(1)))
+        );
+        assert_eq!(
+            fetcher.patch_releases(1, 70).unwrap(),
+            [Channel::Version(1, 70, Some(0)), Channel::Version(1, 70, Some(1))]
+        );
+        assert_eq!(
+            fetcher.patch_releases(1, 71).unwrap(),
+            [Channel::Version(1, 71, Some(0))]
+        );
+        assert!(fetcher.patch_releases(1, 72).unwrap().is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
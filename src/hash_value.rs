@@ -12,6 +12,10 @@ pub enum ParseError {
     /// The hash was not a mutiple of 8-bits (had an odd number of characters)
     #[error("Not a multiple of 8 bits")]
     NotOctetSized,
+
+    /// The hash had no characters
+    #[error("Empty hash")]
+    Empty,
 }
 
 /// A dynamically sized hash used to represent Git SHAs and digest values
@@ -128,3 +132,86 @@ impl std::str::FromStr for HashValue {
         }
     }
 }
+
+/// A possibly abbreviated hash in hexadecimal, such as the short Git commit
+/// hashes printed by `rustc -V`. Unlike a `HashValue`, it may have an odd
+/// number of digits.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AbbreviatedHash {
+    digits: String,
+}
+
+impl AbbreviatedHash {
+    /// Returns `true` if this is `hash` or an abbreviation of it
+    #[must_use]
+    pub fn is_prefix_of(&self, hash: &HashValue) -> bool {
+        hash.to_string().starts_with(self.digits.as_str())
+    }
+}
+
+impl From<&HashValue> for AbbreviatedHash {
+    fn from(hash: &HashValue) -> AbbreviatedHash {
+        AbbreviatedHash {
+            digits: hash.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for AbbreviatedHash {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        formatter.write_str(&self.digits)
+    }
+}
+
+impl std::str::FromStr for AbbreviatedHash {
+    type Err = ParseError;
+
+    fn from_str(string: &str) -> Result<AbbreviatedHash, ParseError> {
+        if string.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        for c in string.bytes() {
+            HashValue::ascii_to_nibble(c)?;
+        }
+        Ok(AbbreviatedHash {
+            digits: string.to_ascii_lowercase(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let hash = HashValue::from_str("871b5952023139738f72eba235063575062bc2e9").unwrap();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, "\"871b5952023139738f72eba235063575062bc2e9\"");
+        assert_eq!(serde_json::from_str::<HashValue>(&json).unwrap(), hash);
+    }
+
+    #[test]
+    fn parses_mixed_case() {
+        let hash = HashValue::from_str("0aFf").unwrap();
+        assert_eq!(hash.as_ref(), &[0x0a, 0xff]);
+        assert_eq!(hash.to_string(), "0aff");
+        assert!(matches!(HashValue::from_str("0g"), Err(ParseError::InvalidByte(b'g'))));
+    }
+
+    #[test]
+    fn abbreviations_may_have_odd_length() {
+        let hash = HashValue::from_str("871b5952023139738f72eba235063575062bc2e9").unwrap();
+        for short in ["871b59520", "871B5952", "8", "871b5952023139738f72eba235063575062bc2e9"] {
+            let short = AbbreviatedHash::from_str(short).unwrap();
+            assert!(short.is_prefix_of(&hash), "{}", short);
+        }
+        assert!(!AbbreviatedHash::from_str("871b59521").unwrap().is_prefix_of(&hash));
+        assert_eq!(AbbreviatedHash::from(&hash).to_string(), hash.to_string());
+        assert!(matches!(AbbreviatedHash::from_str(""), Err(ParseError::Empty)));
+        assert!(matches!(
+            AbbreviatedHash::from_str("87z"),
+            Err(ParseError::InvalidByte(b'z'))
+        ));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::mirror::files_under;
+use crate::{Error, HashValue, Manifest, SupportedTarget};
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+/// An index over the manifests of one channel for many dates, answering
+/// questions about how component availability changed over time without
+/// reparsing the manifests.
+///
+/// The index is built from a directory of manifests with `update`, which only
+/// parses files that are new or have changed since the last update, and is
+/// persisted with `save` and `load`. Components are looked up using the
+/// renames in effect on each date, so `clippy` matches the `clippy-preview`
+/// package on dates where that rename exists.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ManifestHistory {
+    channel: String,
+    keys: Vec<(String, String)>,
+    files: BTreeMap<String, IndexedFile>,
+    dates: BTreeMap<NaiveDate, DateRecord>,
+    #[serde(skip)]
+    key_lookup: HashMap<(String, String), usize>,
+}
+
+/// The state of a manifest file when it was last indexed
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct IndexedFile {
+    size: u64,
+    modified: u64,
+    date: Option<NaiveDate>,
+}
+
+/// What is recorded from the manifest for a date
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct DateRecord {
+    /// The available package builds, as indexes into `ManifestHistory::keys`
+    available: Bitset,
+    git_commits: BTreeSet<String>,
+    renames: BTreeMap<String, String>,
+}
+
+impl DateRecord {
+    fn is_set(&self, index: usize) -> bool {
+        self.available.contains(index)
+    }
+
+    fn set(&mut self, index: usize) {
+        self.available.insert(index);
+    }
+}
+
+/// A set of small integers, serialized as hexadecimal digits which each hold
+/// four members, lowest first
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Bitset {
+    bytes: Vec<u8>,
+}
+
+impl Bitset {
+    fn contains(&self, index: usize) -> bool {
+        self.bytes
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    fn insert(&mut self, index: usize) {
+        if self.bytes.len() <= index / 8 {
+            self.bytes.resize(index / 8 + 1, 0);
+        }
+        self.bytes[index / 8] |= 1 << (index % 8);
+    }
+}
+
+impl Serialize for Bitset {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut digits: String = self
+            .bytes
+            .iter()
+            .flat_map(|byte| [byte & 0xf, byte >> 4])
+            .filter_map(|nibble| char::from_digit(u32::from(nibble), 16))
+            .collect();
+        digits.truncate(digits.trim_end_matches('0').len());
+        serializer.serialize_str(&digits)
+    }
+}
+
+impl<'a> Deserialize<'a> for Bitset {
+    fn deserialize<D>(deserializer: D) -> Result<Bitset, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let digits = String::deserialize(deserializer)?;
+        let nibbles = digits
+            .chars()
+            .map(|c| c.to_digit(16).and_then(|n| u8::try_from(n).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| serde::de::Error::custom(format!("Invalid bitset: {:?}", digits)))?;
+        let bytes = nibbles
+            .chunks(2)
+            .map(|pair| pair[0] | pair.get(1).map_or(0, |high| high << 4))
+            .collect();
+        Ok(Bitset { bytes })
+    }
+}
+
+/// The outcome of updating a manifest history from a directory
+#[derive(Clone, Debug, Default)]
+pub struct HistoryUpdate {
+    /// Manifest files which were (re)parsed
+    pub parsed: Vec<PathBuf>,
+
+    /// Dates which are no longer indexed because their manifests were removed
+    pub removed: Vec<NaiveDate>,
+
+    /// Manifest files which could not be parsed
+    pub unparseable: Vec<PathBuf>,
+}
+
+impl ManifestHistory {
+    /// Constructs an empty history for a channel (e.g. `nightly`)
+    #[must_use]
+    pub fn new(channel: &str) -> ManifestHistory {
+        ManifestHistory {
+            channel: channel.to_string(),
+            keys: Vec::new(),
+            files: BTreeMap::new(),
+            dates: BTreeMap::new(),
+            key_lookup: HashMap::new(),
+        }
+    }
+
+    /// Loads a history previously written with `save`
+    pub fn load(path: &Path) -> Result<ManifestHistory, Error> {
+        let text = std::fs::read_to_string(path)?;
+        let mut history: ManifestHistory = basic_toml::from_str(&text)?;
+        history.key_lookup = history
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key.clone(), index))
+            .collect();
+        Ok(history)
+    }
+
+    /// Writes the history to a file
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let text = basic_toml::to_string(self).map_err(Error::TomlSerialize)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, text)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// The channel the history is for
+    #[must_use]
+    pub fn channel(&self) -> &str {
+        self.channel.as_str()
+    }
+
+    /// Indexes the manifests for the channel found beneath a directory. Only
+    /// files named `channel-rust-<channel>.toml` (as in a mirror) are indexed,
+    /// so other files, such as a saved index, may share the directory. Files
+    /// which have not changed since they were last indexed are not parsed
+    /// again, and dates whose manifests have all been removed are dropped from
+    /// the index.
+    pub fn update(&mut self, root: &Path) -> Result<HistoryUpdate, Error> {
+        let mut report = HistoryUpdate::default();
+        let manifest_name = format!("channel-rust-{}.toml", self.channel);
+        let mut seen = BTreeSet::new();
+        for path in files_under(root)? {
+            if path.file_name().and_then(|n| n.to_str()) != Some(manifest_name.as_str()) {
+                continue;
+            }
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().to_string();
+            seen.insert(relative.clone());
+            let metadata = std::fs::metadata(&path)?;
+            let modified = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let unchanged = self
+                .files
+                .get(&relative)
+                .is_some_and(|file| file.size == metadata.len() && file.modified == modified);
+            if unchanged {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)?;
+            let date = if let Ok(manifest) = Manifest::try_from(text.as_str()) {
+                self.add_manifest(&manifest);
+                report.parsed.push(path.clone());
+                Some(manifest.get_date())
+            } else {
+                report.unparseable.push(path.clone());
+                None
+            };
+            let file = IndexedFile {
+                size: metadata.len(),
+                modified,
+                date,
+            };
+            self.files.insert(relative, file);
+        }
+        self.files.retain(|relative, _| seen.contains(relative));
+        let dates: BTreeSet<NaiveDate> = self.files.values().filter_map(|file| file.date).collect();
+        let removed: Vec<NaiveDate> = self.dates.keys().filter(|d| !dates.contains(d)).copied().collect();
+        for date in &removed {
+            self.dates.remove(date);
+        }
+        report.removed = removed;
+        Ok(report)
+    }
+
+    /// Adds (or replaces) the record for a manifest's date. Dates added this
+    /// way are not associated with a file, so will be dropped by the next
+    /// `update`.
+    pub fn add_manifest(&mut self, manifest: &Manifest) {
+        let mut record = DateRecord::default();
+        for package in manifest.get_package_names() {
+            for (target, available) in manifest.get_package_availability(&package).unwrap_or_default() {
+                if available {
+                    let index = self.key_index(&package, &target.to_string());
+                    record.set(index);
+                }
+            }
+            if let Some(info) = manifest.get_package_info(&package) {
+                record.git_commits.insert(info.git_commit.to_string());
+            }
+        }
+        record.renames = manifest
+            .get_renames()
+            .iter()
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        self.dates.insert(manifest.get_date(), record);
+    }
+
+    fn key_index(&mut self, package: &str, target: &str) -> usize {
+        let key = (package.to_string(), target.to_string());
+        if let Some(index) = self.key_lookup.get(&key) {
+            return *index;
+        }
+        self.keys.push(key.clone());
+        self.key_lookup.insert(key, self.keys.len() - 1);
+        self.keys.len() - 1
+    }
+
+    /// The dates in the index, in ascending order
+    #[must_use]
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        self.dates.keys().copied().collect()
+    }
+
+    /// Returns whether a component was available for a target on a date, or
+    /// `None` if the date is not in the index
+    #[must_use]
+    pub fn is_available(&self, date: NaiveDate, component: &str, target: &SupportedTarget) -> Option<bool> {
+        let record = self.dates.get(&date)?;
+        Some(self.record_has(record, component, target))
+    }
+
+    fn record_has(&self, record: &DateRecord, component: &str, target: &SupportedTarget) -> bool {
+        let package = record.renames.get(component).map_or(component, String::as_str);
+        self.key_lookup
+            .get(&(package.to_string(), target.to_string()))
+            .is_some_and(|index| record.is_set(*index))
+    }
+
+    /// The packages which were available for a target on any indexed date.
+    /// Target-independent packages are only included for
+    /// `SupportedTarget::Independent`.
+    #[must_use]
+    pub fn packages_for_target(&self, target: &SupportedTarget) -> BTreeSet<String> {
+        let target = target.to_string();
+        self.keys
+            .iter()
+            .enumerate()
+            .filter(|(index, (_, key_target))| {
+                *key_target == target && self.dates.values().any(|record| record.is_set(*index))
+            })
+            .map(|(_, (package, _))| package.clone())
+            .collect()
+    }
+
+    /// The targets for which a package was available on any indexed date.
+    /// Targets which can no longer be parsed are omitted.
+    #[must_use]
+    pub fn targets_for_package(&self, package: &str) -> Vec<SupportedTarget> {
+        self.keys
+            .iter()
+            .enumerate()
+            .filter(|(index, (key_package, _))| {
+                key_package == package && self.dates.values().any(|record| record.is_set(*index))
+            })
+            .filter_map(|(_, (_, target))| SupportedTarget::from_str(target).ok())
+            .collect()
+    }
+
+    /// The indexed dates on which a component was available for a target, in
+    /// ascending order
+    #[must_use]
+    pub fn available_dates(&self, component: &str, target: &SupportedTarget) -> Vec<NaiveDate> {
+        self.dates
+            .iter()
+            .filter(|(_, record)| self.record_has(record, component, target))
+            .map(|(date, _)| *date)
+            .collect()
+    }
+
+    /// The first indexed date on which a component was available for a target
+    #[must_use]
+    pub fn first_available(&self, component: &str, target: &SupportedTarget) -> Option<NaiveDate> {
+        self.dates
+            .iter()
+            .find(|(_, record)| self.record_has(record, component, target))
+            .map(|(date, _)| *date)
+    }
+
+    /// The last indexed date on which a component was available for a target
+    #[must_use]
+    pub fn last_available(&self, component: &str, target: &SupportedTarget) -> Option<NaiveDate> {
+        self.dates
+            .iter()
+            .rev()
+            .find(|(_, record)| self.record_has(record, component, target))
+            .map(|(date, _)| *date)
+    }
+
+    /// The periods between the first and last availability of a component for
+    /// a target during which it was unavailable. Each period is given as the
+    /// first and last indexed dates (inclusive) on which it was unavailable.
+    #[must_use]
+    pub fn availability_gaps(&self, component: &str, target: &SupportedTarget) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut gaps = Vec::new();
+        let mut seen_available = false;
+        let mut gap: Option<(NaiveDate, NaiveDate)> = None;
+        for (date, record) in &self.dates {
+            if self.record_has(record, component, target) {
+                gaps.extend(gap.take().filter(|_| seen_available));
+                seen_available = true;
+            } else {
+                gap = Some(gap.map_or((*date, *date), |(start, _)| (start, *date)));
+            }
+        }
+        gaps
+    }
+
+    /// Returns the first indexed date on which a rename of `from` appeared,
+    /// along with the name it was renamed to
+    #[must_use]
+    pub fn rename_first_seen(&self, from: &str) -> Option<(NaiveDate, &str)> {
+        self.dates
+            .iter()
+            .find_map(|(date, record)| record.renames.get(from).map(|to| (*date, to.as_str())))
+    }
+
+    /// The indexed dates on whose manifests a package was built from a git
+    /// commit, in ascending order
+    #[must_use]
+    pub fn dates_with_commit(&self, git_commit: &HashValue) -> Vec<NaiveDate> {
+        let git_commit = git_commit.to_string();
+        self.dates
+            .iter()
+            .filter(|(_, record)| record.git_commits.contains(&git_commit))
+            .map(|(date, _)| *date)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::manifest_text;
+
+    #[test]
+    fn bitset_serializes_as_hexadecimal() {
+        let mut bitset = Bitset::default();
+        for index in [0, 5, 17] {
+            bitset.insert(index);
+        }
+        assert!(bitset.contains(5) && !bitset.contains(4) && !bitset.contains(1000));
+        let json = serde_json::to_string(&bitset).unwrap();
+        assert_eq!(json, "\"12002\"");
+        assert_eq!(serde_json::from_str::<Bitset>(&json).unwrap(), bitset);
+        assert!(serde_json::from_str::<Bitset>("\"1x\"").is_err());
+    }
+
+    #[test]
+    fn update_only_indexes_channel_manifests() {
+        let root = std::env::temp_dir().join(format!("rtm-history-test-{}", std::process::id()));
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        for (date, available) in [("2023-06-01", true), ("2023-06-02", false)] {
+            let text = manifest_text(
+                date,
+                "1.72.0-nightly",
+                commit,
+                "x86_64-unknown-linux-gnu",
+                &[("clippy", available)],
+            );
+            let path = root.join("dist").join(date).join("channel-rust-nightly.toml");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        let beta = manifest_text("2023-06-03", "1.71.0-beta.1", commit, "x86_64-unknown-linux-gnu", &[]);
+        std::fs::write(root.join("dist").join("channel-rust-beta.toml"), beta).unwrap();
+
+        let mut history = ManifestHistory::new("nightly");
+        let index = root.join("index.toml");
+        assert_eq!(history.update(&root).unwrap().parsed.len(), 2);
+        history.save(&index).unwrap();
+        let mut history = ManifestHistory::load(&index).unwrap();
+        let update = history.update(&root).unwrap();
+        assert!(update.parsed.is_empty() && update.unparseable.is_empty());
+
+        let host = SupportedTarget::from_str("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(history.dates().len(), 2);
+        assert_eq!(
+            history.available_dates("clippy", &host),
+            [NaiveDate::from_ymd_opt(2023, 6, 1).unwrap()]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,789 @@
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use target_lexicon::{OperatingSystem, Triple};
+use thiserror::Error;
+
+/// Architectures recognised in abbreviated host triples, as by Rustup
+const ARCHITECTURES: [&str; 18] = [
+    "i386",
+    "i586",
+    "i686",
+    "x86_64",
+    "arm",
+    "armv7",
+    "armv7s",
+    "aarch64",
+    "mips",
+    "mipsel",
+    "mips64",
+    "mips64el",
+    "powerpc",
+    "powerpc64",
+    "powerpc64le",
+    "riscv64gc",
+    "s390x",
+    "loongarch64",
+];
+
+/// Operating systems (with their vendors) recognised in abbreviated host
+/// triples, as by Rustup
+const OPERATING_SYSTEMS: [&str; 9] = [
+    "pc-windows",
+    "unknown-linux",
+    "apple-darwin",
+    "unknown-netbsd",
+    "apple-ios",
+    "linux",
+    "rumprun-netbsd",
+    "unknown-freebsd",
+    "unknown-illumos",
+];
+
+/// Environments recognised in abbreviated host triples, as by Rustup
+const ENVIRONMENTS: [&str; 9] = [
+    "gnu",
+    "gnux32",
+    "msvc",
+    "gnueabi",
+    "gnueabihf",
+    "gnuabi64",
+    "androideabi",
+    "android",
+    "musl",
+];
+
+/// Errors which can occur while completing a partial host triple
+#[derive(Clone, Debug, Error)]
+pub enum HostError {
+    /// The partial triple was not recognised
+    #[error("Unrecognised host: {0}")]
+    Unrecognised(String),
+
+    /// None of the candidate hosts matched the partial triple
+    #[error("No host matches {0}")]
+    NoMatch(String),
+
+    /// Several candidate hosts matched the partial triple
+    #[error("Host {0} is ambiguous between {}", .1.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Ambiguous(String, Vec<Triple>),
+
+    /// The completed triple could not be parsed
+    #[error("Failed to parse target triple: {0}")]
+    Target(#[from] target_lexicon::ParseError),
+}
+
+/// A possibly abbreviated host triple, as accepted by Rustup in toolchain
+/// names (e.g. `msvc`, `gnu`, `x86_64-pc-windows` or `i686-msvc`). Each part
+/// is either given or left to be completed from the host.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PartialTriple {
+    /// The architecture (e.g. `x86_64`)
+    pub arch: Option<String>,
+
+    /// The vendor and operating system (e.g. `pc-windows`)
+    pub os: Option<String>,
+
+    /// The environment (e.g. `msvc`)
+    pub env: Option<String>,
+}
+
+/// Strips an optional leading part from `string`, returning each way it could
+/// be split into that part and the rest. Splitting with the part present is
+/// preferred.
+fn strip_part<'a>(string: &'a str, options: &[&'static str]) -> Vec<(Option<&'static str>, &'a str)> {
+    let mut result: Vec<(Option<&'static str>, &'a str)> = options
+        .iter()
+        .filter_map(|option| {
+            let rest = string.strip_prefix(option)?;
+            if rest.is_empty() {
+                Some((Some(*option), rest))
+            } else {
+                rest.strip_prefix('-').map(|rest| (Some(*option), rest))
+            }
+        })
+        .collect();
+    result.push((None, string));
+    result
+}
+
+impl PartialTriple {
+    /// Splits a full triple into its parts. Triples using parts Rustup does not
+    /// recognise are split by position.
+    #[must_use]
+    pub fn from_triple(triple: &Triple) -> PartialTriple {
+        let string = triple.to_string();
+        if let Ok(partial) = PartialTriple::from_str(&string) {
+            if partial.arch.is_some() && partial.os.is_some() {
+                return partial;
+            }
+        }
+        let components: Vec<&str> = string.split('-').collect();
+        let (os, env) = match components.len() {
+            0 | 1 => (None, None),
+            2 | 3 => (Some(components[1..].join("-")), None),
+            n => (
+                Some(components[1..n - 1].join("-")),
+                Some(components[n - 1].to_string()),
+            ),
+        };
+        PartialTriple {
+            arch: components.first().map(ToString::to_string),
+            os,
+            env,
+        }
+    }
+
+    /// Returns whether every part given in this partial triple matches the
+    /// corresponding part of a full triple
+    #[must_use]
+    pub fn matches(&self, triple: &Triple) -> bool {
+        let full = PartialTriple::from_triple(triple);
+        let part_matches = |part: &Option<String>, full: &Option<String>| part.is_none() || part == full;
+        part_matches(&self.arch, &full.arch) && part_matches(&self.os, &full.os) && part_matches(&self.env, &full.env)
+    }
+
+    /// Completes the triple from a host, as Rustup does: a missing
+    /// architecture or operating system is taken from the host, but the
+    /// host's environment is only used if neither of those was given (so
+    /// `x86_64-pc-windows` has no environment, while `msvc` takes the host's
+    /// architecture and operating system).
+    pub fn complete(&self, host: &Triple) -> Result<Triple, HostError> {
+        let host = PartialTriple::from_triple(host);
+        let arch = self.arch.as_ref().or(host.arch.as_ref());
+        let os = self.os.as_ref().or(host.os.as_ref());
+        let env = if self.arch.is_some() || self.os.is_some() {
+            self.env.as_ref()
+        } else {
+            self.env.as_ref().or(host.env.as_ref())
+        };
+        let parts: Vec<&str> = [arch, os, env].into_iter().flatten().map(String::as_str).collect();
+        Ok(Triple::from_str(&parts.join("-"))?)
+    }
+
+    /// Completes the triple by choosing among candidate hosts (e.g. those a
+    /// manifest is available for). If several candidates match, those which
+    /// share the most of the parts not given with `host` are preferred; if
+    /// that still leaves several, the triple is ambiguous.
+    pub fn complete_from(&self, host: &Triple, candidates: &[Triple]) -> Result<Triple, HostError> {
+        let host = PartialTriple::from_triple(host);
+        let shared_with_host = |candidate: &Triple| {
+            let candidate = PartialTriple::from_triple(candidate);
+            [
+                (&self.arch, &candidate.arch, &host.arch),
+                (&self.os, &candidate.os, &host.os),
+                (&self.env, &candidate.env, &host.env),
+            ]
+            .into_iter()
+            .filter(|(given, candidate, host)| given.is_none() && candidate == host)
+            .count()
+        };
+        let matching: Vec<(&Triple, usize)> = candidates
+            .iter()
+            .filter(|candidate| self.matches(candidate))
+            .map(|candidate| (candidate, shared_with_host(candidate)))
+            .collect();
+        let most_shared = matching.iter().map(|(_, shared)| *shared).max();
+        let preferred: Vec<Triple> = matching
+            .into_iter()
+            .filter(|(_, shared)| Some(*shared) == most_shared)
+            .map(|(candidate, _)| candidate.clone())
+            .collect();
+        match preferred.len() {
+            0 => Err(HostError::NoMatch(self.to_string())),
+            1 => Ok(preferred.into_iter().next().expect("One preferred candidate")),
+            _ => Err(HostError::Ambiguous(self.to_string(), preferred)),
+        }
+    }
+}
+
+impl FromStr for PartialTriple {
+    type Err = HostError;
+
+    fn from_str(string: &str) -> Result<PartialTriple, HostError> {
+        for (arch, rest) in strip_part(string, &ARCHITECTURES) {
+            for (os, rest) in strip_part(rest, &OPERATING_SYSTEMS) {
+                for (env, rest) in strip_part(rest, &ENVIRONMENTS) {
+                    if rest.is_empty() && (arch.is_some() || os.is_some() || env.is_some()) {
+                        return Ok(PartialTriple {
+                            arch: arch.map(ToString::to_string),
+                            os: os.map(ToString::to_string),
+                            env: env.map(ToString::to_string),
+                        });
+                    }
+                }
+            }
+        }
+        Err(HostError::Unrecognised(string.to_string()))
+    }
+}
+
+impl std::fmt::Display for PartialTriple {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let parts: Vec<&str> = [&self.arch, &self.os, &self.env]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        write!(formatter, "{}", parts.join("-"))
+    }
+}
+
+/// The ELF program header type of the program interpreter path
+const PT_INTERP: usize = 3;
+
+/// The word size and dynamic loader of an ELF executable
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ElfInfo {
+    /// Whether the executable is 32-bit
+    pub is_32bit: bool,
+
+    /// The path of the program interpreter (dynamic loader), if any
+    pub interpreter: Option<String>,
+}
+
+impl ElfInfo {
+    /// Reads the header of an ELF executable. Returns `None` if the file
+    /// cannot be read, is not a little- or big-endian ELF file, or has
+    /// program headers which lie outside it.
+    #[must_use]
+    pub fn read(path: &Path) -> Option<ElfInfo> {
+        let data = std::fs::read(path).ok()?;
+        if data.get(..4)? != b"\x7fELF" {
+            return None;
+        }
+        let is_32bit = match data.get(4)? {
+            1 => true,
+            2 => false,
+            _ => return None,
+        };
+        let big_endian = match data.get(5)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let read = |offset: usize, size: usize| -> Option<usize> {
+            let bytes = data.get(offset..offset.checked_add(size)?)?;
+            let fold = |value: u64, byte: &u8| (value << 8) | u64::from(*byte);
+            let value = if big_endian {
+                bytes.iter().fold(0, fold)
+            } else {
+                bytes.iter().rev().fold(0, fold)
+            };
+            usize::try_from(value).ok()
+        };
+        // Offsets of the program header table fields, and of the offset and
+        // size fields within each program header
+        let (program_headers, entry_size, entry_count, word) = if is_32bit {
+            (read(0x1c, 4)?, read(0x2a, 2)?, read(0x2c, 2)?, 4)
+        } else {
+            (read(0x20, 8)?, read(0x36, 2)?, read(0x38, 2)?, 8)
+        };
+        let (offset_field, size_field) = if is_32bit { (0x04, 0x10) } else { (0x08, 0x20) };
+        let mut interpreter = None;
+        for index in 0..entry_count {
+            let header = index.checked_mul(entry_size)?.checked_add(program_headers)?;
+            if read(header, 4)? != PT_INTERP {
+                continue;
+            }
+            let offset = read(header.checked_add(offset_field)?, word)?;
+            let size = read(header.checked_add(size_field)?, word)?;
+            let bytes = data.get(offset..offset.checked_add(size)?)?;
+            let path = bytes.split(|b| *b == 0).next().unwrap_or_default();
+            interpreter = Some(String::from_utf8_lossy(path).to_string());
+        }
+        Some(ElfInfo { is_32bit, interpreter })
+    }
+}
+
+/// Facts about the running system used to refine the compile-time host
+/// triple, as Rustup does
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HostFacts {
+    /// The machine architecture reported by the kernel (`uname -m`)
+    pub kernel_arch: Option<String>,
+
+    /// The word size and dynamic loader of the system shell, which indicate
+    /// the userland's word size and C library on Linux
+    pub shell: Option<ElfInfo>,
+
+    /// Whether the process is being translated by Rosetta on an Apple
+    /// silicon Mac
+    pub translated: bool,
+
+    /// The native processor architecture of a Windows system running a
+    /// 32-bit or emulated process (`PROCESSOR_ARCHITEW6432`)
+    pub windows_native_arch: Option<String>,
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|s| !s.is_empty())
+}
+
+impl HostFacts {
+    /// Gathers facts about the running system. Facts which cannot be
+    /// determined are left unset.
+    #[must_use]
+    pub fn probe() -> HostFacts {
+        let mut facts = HostFacts::default();
+        match target_lexicon::HOST.operating_system {
+            OperatingSystem::Linux => {
+                facts.kernel_arch = command_output("uname", &["-m"]);
+                facts.shell = ElfInfo::read(Path::new("/bin/sh"));
+            }
+            OperatingSystem::Darwin | OperatingSystem::MacOSX { .. } => {
+                facts.translated = command_output("sysctl", &["-n", "sysctl.proc_translated"]).as_deref() == Some("1");
+            }
+            OperatingSystem::Windows => {
+                facts.windows_native_arch = std::env::var("PROCESSOR_ARCHITEW6432").ok();
+            }
+            _ => {}
+        }
+        facts
+    }
+
+    /// Refines a host triple using these facts:
+    ///
+    /// - on Linux, the architecture is taken from the kernel, unless the
+    ///   userland is 32-bit on a 64-bit kernel, and the environment is `musl`
+    ///   or `gnu` according to the shell's dynamic loader;
+    /// - on macOS, a process translated by Rosetta is on `aarch64`;
+    /// - on Windows, a 32-bit or emulated process reports the native
+    ///   architecture.
+    ///
+    /// Triples which would not parse after refinement are returned unchanged.
+    #[must_use]
+    pub fn refine(&self, host: &Triple) -> Triple {
+        let mut parts = PartialTriple::from_triple(host);
+        match host.operating_system {
+            OperatingSystem::Linux => self.refine_linux(&mut parts),
+            OperatingSystem::Darwin | OperatingSystem::MacOSX { .. } if self.translated => {
+                parts.arch = Some("aarch64".to_string());
+            }
+            OperatingSystem::Windows => {
+                let arch = match self.windows_native_arch.as_deref() {
+                    Some("AMD64") => Some("x86_64"),
+                    Some("ARM64") => Some("aarch64"),
+                    _ => None,
+                };
+                if let Some(arch) = arch {
+                    parts.arch = Some(arch.to_string());
+                }
+            }
+            _ => {}
+        }
+        Triple::from_str(&parts.to_string()).unwrap_or_else(|_| host.clone())
+    }
+
+    fn refine_linux(&self, parts: &mut PartialTriple) {
+        let userland_32bit = self.shell.as_ref().is_some_and(|shell| shell.is_32bit);
+        let arch = match (self.kernel_arch.as_deref(), userland_32bit) {
+            (Some("x86_64" | "amd64"), true) | (Some("i686" | "i586" | "i386"), _) => Some("i686"),
+            (Some("x86_64" | "amd64"), false) => Some("x86_64"),
+            (Some("aarch64" | "arm64"), true) | (Some("armv7l" | "armv8l"), _) => Some("armv7"),
+            (Some("aarch64" | "arm64"), false) => Some("aarch64"),
+            (Some("ppc64le"), false) => Some("powerpc64le"),
+            (Some("ppc64"), true) | (Some("ppc"), _) => Some("powerpc"),
+            (Some("ppc64"), false) => Some("powerpc64"),
+            (Some("riscv64"), false) => Some("riscv64gc"),
+            (Some("s390x"), false) => Some("s390x"),
+            (Some("loongarch64"), false) => Some("loongarch64"),
+            _ => None,
+        };
+        if let Some(arch) = arch {
+            parts.arch = Some(arch.to_string());
+        }
+        let interpreter = self.shell.as_ref().and_then(|shell| shell.interpreter.as_deref());
+        let libc = match interpreter {
+            Some(interpreter) if interpreter.contains("musl") => Some("musl"),
+            Some(interpreter) if interpreter.contains("ld-linux") || interpreter.contains("ld64") => Some("gnu"),
+            _ => None,
+        };
+        if let Some(libc) = libc {
+            let suffix = if parts.arch.as_deref().is_some_and(|arch| arch.starts_with("arm")) {
+                "eabihf"
+            } else {
+                ""
+            };
+            parts.env = Some(format!("{}{}", libc, suffix));
+        }
+    }
+}
+
+/// Detects the host triple of the running system. This starts from the triple
+/// this crate was compiled for and refines it with `HostFacts::probe`, so a
+/// 32-bit build on a 64-bit system, a glibc build on a musl system, or an
+/// x86-64 macOS build under Rosetta still reports the native host.
+#[must_use]
+pub fn detect_host() -> Triple {
+    HostFacts::probe().refine(&target_lexicon::HOST)
+}
+
+/// Compatible hosts whose builds of a package can be used when the native
+/// build is unavailable, for use with `Manifest::plan_install_with_fallbacks`.
+/// Fallbacks for a host are tried in the order they were added.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HostFallbacks {
+    fallbacks: Vec<(Triple, Triple)>,
+}
+
+impl HostFallbacks {
+    /// Constructs an empty fallback table
+    #[must_use]
+    pub fn new() -> HostFallbacks {
+        HostFallbacks::default()
+    }
+
+    /// Constructs a table of commonly usable fallbacks: x86-64 macOS builds
+    /// under Rosetta on Apple silicon, x86-64 (emulated) and 32-bit builds on
+    /// Windows, and statically linked musl builds on glibc Linux hosts
+    #[must_use]
+    pub fn standard() -> HostFallbacks {
+        const STANDARD: [(&str, &str); 8] = [
+            ("aarch64-apple-darwin", "x86_64-apple-darwin"),
+            ("aarch64-pc-windows-msvc", "x86_64-pc-windows-msvc"),
+            ("aarch64-pc-windows-msvc", "i686-pc-windows-msvc"),
+            ("x86_64-pc-windows-msvc", "i686-pc-windows-msvc"),
+            ("x86_64-pc-windows-gnu", "i686-pc-windows-gnu"),
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"),
+            ("aarch64-unknown-linux-gnu", "aarch64-unknown-linux-musl"),
+            ("i686-unknown-linux-gnu", "i686-unknown-linux-musl"),
+        ];
+        let fallbacks = STANDARD
+            .iter()
+            .filter_map(|(host, fallback)| Some((Triple::from_str(host).ok()?, Triple::from_str(fallback).ok()?)))
+            .collect();
+        HostFallbacks { fallbacks }
+    }
+
+    /// Adds a fallback for a host
+    #[must_use]
+    pub fn with_fallback(mut self, host: Triple, fallback: Triple) -> HostFallbacks {
+        self.fallbacks.push((host, fallback));
+        self
+    }
+
+    /// The fallbacks for a host, in order of preference
+    #[must_use]
+    pub fn fallbacks_for(&self, host: &Triple) -> Vec<&Triple> {
+        self.fallbacks
+            .iter()
+            .filter(|(from, _)| from == host)
+            .map(|(_, fallback)| fallback)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `value` as a `size`-byte integer at `offset`
+    fn put(data: &mut Vec<u8>, offset: usize, size: usize, value: u64, big_endian: bool) {
+        if data.len() < offset + size {
+            data.resize(offset + size, 0);
+        }
+        let field = &mut data[offset..offset + size];
+        field.copy_from_slice(&value.to_le_bytes()[..size]);
+        if big_endian {
+            field.reverse();
+        }
+    }
+
+    /// Builds an ELF file with a single program header, which is a
+    /// `PT_INTERP` header for `interpreter` located at `interpreter_offset`
+    fn elf(is_32bit: bool, big_endian: bool, interpreter: &[u8], interpreter_offset: u64) -> Vec<u8> {
+        let mut data = b"\x7fELF".to_vec();
+        data.push(if is_32bit { 1 } else { 2 });
+        data.push(if big_endian { 2 } else { 1 });
+        let (header_size, entry_size) = if is_32bit { (0x34, 0x20) } else { (0x40, 0x38) };
+        let fields = if is_32bit {
+            [(0x1c, 4), (0x2a, 2), (0x2c, 2), (0x04, 4), (0x10, 4)]
+        } else {
+            [(0x20, 8), (0x36, 2), (0x38, 2), (0x08, 8), (0x20, 8)]
+        };
+        let [program_headers, entry_size_field, entry_count, offset_field, size_field] = fields;
+        put(&mut data, program_headers.0, program_headers.1, header_size, big_endian);
+        put(
+            &mut data,
+            entry_size_field.0,
+            entry_size_field.1,
+            entry_size,
+            big_endian,
+        );
+        put(&mut data, entry_count.0, entry_count.1, 1, big_endian);
+        let header = usize::try_from(header_size).unwrap();
+        put(&mut data, header, 4, PT_INTERP as u64, big_endian);
+        put(
+            &mut data,
+            header + offset_field.0,
+            offset_field.1,
+            interpreter_offset,
+            big_endian,
+        );
+        put(
+            &mut data,
+            header + size_field.0,
+            size_field.1,
+            interpreter.len() as u64,
+            big_endian,
+        );
+        data.resize(header + usize::try_from(entry_size).unwrap(), 0);
+        data.extend_from_slice(interpreter);
+        data
+    }
+
+    fn read_bytes(name: &str, data: &[u8]) -> Option<ElfInfo> {
+        let path = std::env::temp_dir().join(format!("rtm-elf-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        let info = ElfInfo::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        info
+    }
+
+    #[test]
+    fn reads_elf_interpreter() {
+        let interpreter = b"/lib64/ld-linux-x86-64.so.2\0";
+        let data = elf(false, false, interpreter, 0x78);
+        let info = read_bytes("64", &data).unwrap();
+        assert!(!info.is_32bit);
+        assert_eq!(info.interpreter.as_deref(), Some("/lib64/ld-linux-x86-64.so.2"));
+
+        let interpreter = b"/lib/ld.so.1\0";
+        let data = elf(true, true, interpreter, 0x54);
+        let info = read_bytes("32", &data).unwrap();
+        assert!(info.is_32bit);
+        assert_eq!(info.interpreter.as_deref(), Some("/lib/ld.so.1"));
+    }
+
+    #[test]
+    fn rejects_malformed_elf() {
+        assert_eq!(read_bytes("empty", b""), None);
+        assert_eq!(read_bytes("text", b"#!/bin/sh\n"), None);
+        assert_eq!(
+            read_bytes("truncated", &elf(false, false, b"/lib/ld.so\0", 0x78)[..0x50]),
+            None
+        );
+        assert_eq!(
+            read_bytes("outside", &elf(false, false, b"/lib/ld.so\0", u64::MAX)),
+            None
+        );
+
+        let mut data = elf(false, false, b"", 0x78);
+        put(&mut data, 0x20, 8, u64::MAX, false);
+        assert_eq!(read_bytes("headers", &data), None);
+        let mut data = elf(false, false, b"", 0x78);
+        put(&mut data, 0x20, 8, u64::MAX - 0x10, false);
+        put(&mut data, 0x36, 2, u64::from(u16::MAX), false);
+        put(&mut data, 0x38, 2, u64::from(u16::MAX), false);
+        assert_eq!(read_bytes("entries", &data), None);
+    }
+
+    fn triple(name: &str) -> Triple {
+        Triple::from_str(name).unwrap()
+    }
+
+    fn partial(name: &str) -> PartialTriple {
+        PartialTriple::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn parses_partial_triples() {
+        let expected = |arch: Option<&str>, os: Option<&str>, env: Option<&str>| PartialTriple {
+            arch: arch.map(ToString::to_string),
+            os: os.map(ToString::to_string),
+            env: env.map(ToString::to_string),
+        };
+        assert_eq!(partial("msvc"), expected(None, None, Some("msvc")));
+        assert_eq!(partial("i686-msvc"), expected(Some("i686"), None, Some("msvc")));
+        assert_eq!(
+            partial("x86_64-pc-windows"),
+            expected(Some("x86_64"), Some("pc-windows"), None)
+        );
+        // `gnueabihf` is preferred over `gnu` followed by a remainder
+        assert_eq!(
+            partial("armv7-gnueabihf"),
+            expected(Some("armv7"), None, Some("gnueabihf"))
+        );
+        for name in ["", "windows", "msvc-x86_64"] {
+            assert!(
+                matches!(PartialTriple::from_str(name), Err(HostError::Unrecognised(_))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn completes_partial_triples_from_host() {
+        let windows = triple("x86_64-pc-windows-gnu");
+        let complete = |name: &str, host: &Triple| partial(name).complete(host).unwrap().to_string();
+        assert_eq!(complete("msvc", &windows), "x86_64-pc-windows-msvc");
+        assert_eq!(complete("i686", &windows), "i686-pc-windows");
+        assert_eq!(complete("x86_64-pc-windows", &windows), "x86_64-pc-windows");
+        assert_eq!(
+            complete("gnu", &triple("aarch64-unknown-linux-musl")),
+            "aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(complete("i686-msvc", &windows), "i686-pc-windows-msvc");
+    }
+
+    #[test]
+    fn completes_partial_triples_from_candidates() {
+        let candidates: Vec<Triple> = [
+            "x86_64-unknown-linux-gnu",
+            "x86_64-pc-windows-msvc",
+            "x86_64-pc-windows-gnu",
+            "i686-pc-windows-msvc",
+        ]
+        .into_iter()
+        .map(triple)
+        .collect();
+        let linux = triple("x86_64-unknown-linux-gnu");
+        let complete_from = |name: &str, host: &Triple| partial(name).complete_from(host, &candidates);
+        assert_eq!(complete_from("msvc", &linux).unwrap(), candidates[1]);
+        assert_eq!(complete_from("i686", &linux).unwrap(), candidates[3]);
+        assert_eq!(complete_from("pc-windows", &linux).unwrap(), candidates[2]);
+        assert_eq!(complete_from("x86_64", &linux).unwrap(), candidates[0]);
+
+        match complete_from("pc-windows", &triple("aarch64-apple-darwin")) {
+            Err(HostError::Ambiguous(partial, matching)) => {
+                assert_eq!(partial, "pc-windows");
+                assert_eq!(matching, candidates[1..]);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            complete_from("aarch64-msvc", &linux),
+            Err(HostError::NoMatch(partial)) if partial == "aarch64-msvc"
+        ));
+    }
+
+    fn assert_refines(cases: &[(&str, HostFacts, &str)]) {
+        for (host, facts, expected) in cases {
+            assert_eq!(
+                facts.refine(&triple(host)).to_string(),
+                *expected,
+                "{} {:?}",
+                host,
+                facts
+            );
+        }
+    }
+
+    #[test]
+    fn refines_linux_host_from_facts() {
+        let shell = |is_32bit: bool, interpreter: &str| {
+            Some(ElfInfo {
+                is_32bit,
+                interpreter: Some(interpreter.to_string()),
+            })
+        };
+        let linux = |kernel_arch: Option<&str>, shell: Option<ElfInfo>| HostFacts {
+            kernel_arch: kernel_arch.map(ToString::to_string),
+            shell,
+            ..HostFacts::default()
+        };
+        let cases = [
+            (
+                "x86_64-unknown-linux-gnu",
+                linux(Some("x86_64"), shell(false, "/lib/ld-musl-x86_64.so.1")),
+                "x86_64-unknown-linux-musl",
+            ),
+            (
+                "x86_64-unknown-linux-musl",
+                linux(Some("x86_64"), shell(false, "/lib64/ld-linux-x86-64.so.2")),
+                "x86_64-unknown-linux-gnu",
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                linux(Some("x86_64"), shell(true, "/lib/ld-linux.so.2")),
+                "i686-unknown-linux-gnu",
+            ),
+            (
+                "i686-unknown-linux-gnu",
+                linux(Some("amd64"), shell(false, "/lib64/ld-linux-x86-64.so.2")),
+                "x86_64-unknown-linux-gnu",
+            ),
+            (
+                "aarch64-unknown-linux-gnu",
+                linux(Some("aarch64"), shell(true, "/lib/ld-linux-armhf.so.3")),
+                "armv7-unknown-linux-gnueabihf",
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                linux(Some("aarch64"), shell(false, "/lib/ld-linux-aarch64.so.1")),
+                "aarch64-unknown-linux-gnu",
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                linux(Some("ppc64le"), None),
+                "powerpc64le-unknown-linux-gnu",
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                linux(Some("mystery"), None),
+                "x86_64-unknown-linux-gnu",
+            ),
+            (
+                "x86_64-unknown-linux-musl",
+                HostFacts::default(),
+                "x86_64-unknown-linux-musl",
+            ),
+        ];
+        assert_refines(&cases);
+    }
+
+    #[test]
+    fn refines_macos_and_windows_hosts_from_facts() {
+        let cases = [
+            (
+                "x86_64-apple-darwin",
+                HostFacts {
+                    translated: true,
+                    ..HostFacts::default()
+                },
+                "aarch64-apple-darwin",
+            ),
+            ("x86_64-apple-darwin", HostFacts::default(), "x86_64-apple-darwin"),
+            (
+                "i686-pc-windows-msvc",
+                HostFacts {
+                    windows_native_arch: Some("AMD64".to_string()),
+                    ..HostFacts::default()
+                },
+                "x86_64-pc-windows-msvc",
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                HostFacts {
+                    windows_native_arch: Some("ARM64".to_string()),
+                    ..HostFacts::default()
+                },
+                "aarch64-pc-windows-msvc",
+            ),
+            (
+                "i686-pc-windows-msvc",
+                HostFacts {
+                    windows_native_arch: Some("x86".to_string()),
+                    ..HostFacts::default()
+                },
+                "i686-pc-windows-msvc",
+            ),
+            (
+                "x86_64-unknown-freebsd",
+                HostFacts {
+                    kernel_arch: Some("aarch64".to_string()),
+                    translated: true,
+                    ..HostFacts::default()
+                },
+                "x86_64-unknown-freebsd",
+            ),
+        ];
+        assert_refines(&cases);
+    }
+}
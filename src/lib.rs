@@ -6,22 +6,77 @@
 )]
 #![forbid(unsafe_code)]
 
+/// Parsing of Rustup-style install arguments
+pub mod args;
+
+/// Release dates of Rust versions
+pub mod calendar;
+
+/// Comparison of manifests
+pub mod diff;
+
 mod error;
 
+/// Retrieval of manifests and binaries from distribution servers
+pub mod fetch;
+
+/// Host triples and their abbreviations
+pub mod host;
+
+/// Indexes over the manifests of a channel for many dates
+pub mod history;
+
 /// Types related to digest values
 pub mod hash_value;
 
+/// Consistency checks for manifests
+pub mod lint;
+
 /// Types related to toolchain manifests
 pub mod manifest;
 
 mod manifest_v2;
+
+/// Planning of installs for a workspace's minimum supported Rust version
+pub mod msrv;
+
+/// Searches over nightly toolchains
+pub mod nightly;
+
+/// Local mirrors of distribution servers
+pub mod mirror;
+
+/// Install plans produced by resolving install specifications
+pub mod plan;
+
+/// Pull-through caching of distribution servers
+pub mod proxy;
+
+/// Component availability reports
+pub mod report;
+
+/// Serving of mirror directories over HTTP
+pub mod server;
+
+/// Install specifications
+pub mod spec;
+
 mod supported_target;
 
+#[cfg(test)]
+mod testing;
+
 /// Types related to toolchain specification
 pub mod toolchain;
 
+/// Parsing of package version strings
+pub mod version;
+
 pub use error::Error;
+pub use fetch::{Fetcher, ManifestFetcher};
 pub use hash_value::HashValue;
-pub use manifest::{InstallSpec, Manifest};
+pub use manifest::Manifest;
+pub use plan::InstallPlan;
+pub use spec::{ComponentName, InstallSpec};
 pub use supported_target::SupportedTarget;
 pub use toolchain::Toolchain;
@@ -0,0 +1,465 @@
+use crate::version::VersionInconsistency;
+use crate::{Error, Manifest, SupportedTarget};
+use std::collections::BTreeSet;
+use target_lexicon::Triple;
+
+/// An internal inconsistency found in a manifest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LintIssue {
+    /// A profile listed a component that is not known on any host
+    UnknownProfileComponent {
+        /// The profile name
+        profile: String,
+
+        /// The component name
+        component: String,
+    },
+
+    /// A rename referred to a package that is not in the manifest
+    DanglingRename {
+        /// The old package name
+        from: String,
+
+        /// The new package name
+        to: String,
+    },
+
+    /// A host listed a component whose package is not in the manifest
+    UnknownComponentPackage {
+        /// The host listing the component
+        host: Triple,
+
+        /// The package name
+        package: String,
+
+        /// The target of the package
+        target: SupportedTarget,
+    },
+
+    /// A host listed a (non-extension) component whose package is not
+    /// available for the component's target
+    UnavailableComponent {
+        /// The host listing the component
+        host: Triple,
+
+        /// The package name
+        package: String,
+
+        /// The target of the package
+        target: SupportedTarget,
+    },
+
+    /// A package with available builds had no version information
+    MissingVersion {
+        /// The package name
+        package: String,
+    },
+
+    /// An available package build had no tarballs
+    MissingTarball {
+        /// The package name
+        package: String,
+
+        /// The target of the package
+        target: SupportedTarget,
+    },
+
+    /// A package's version string could not be parsed
+    UnparseableVersion {
+        /// The package name
+        package: String,
+
+        /// The version string
+        version: String,
+    },
+
+    /// A package's version string disagreed with its git commit or the
+    /// manifest date
+    InconsistentVersion {
+        /// The package name
+        package: String,
+
+        /// The inconsistency
+        inconsistency: VersionInconsistency,
+    },
+
+    /// A tarball was not located in the directory for the manifest's date
+    UrlDateMismatch {
+        /// The package name
+        package: String,
+
+        /// The tarball URL
+        url: String,
+    },
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LintIssue::UnknownProfileComponent { profile, component } => {
+                write!(formatter, "Profile {} lists unknown component {}", profile, component)
+            }
+            LintIssue::DanglingRename { from, to } => {
+                write!(formatter, "Rename of {} refers to missing package {}", from, to)
+            }
+            LintIssue::UnknownComponentPackage { host, package, target } => {
+                write!(
+                    formatter,
+                    "Host {} lists component {} ({}) with no package",
+                    host, package, target
+                )
+            }
+            LintIssue::UnavailableComponent { host, package, target } => {
+                write!(
+                    formatter,
+                    "Host {} lists unavailable component {} ({})",
+                    host, package, target
+                )
+            }
+            LintIssue::MissingVersion { package } => {
+                write!(formatter, "Package {} is available but has no version", package)
+            }
+            LintIssue::MissingTarball { package, target } => {
+                write!(
+                    formatter,
+                    "Package {} ({}) is available but has no tarballs",
+                    package, target
+                )
+            }
+            LintIssue::UnparseableVersion { package, version } => {
+                write!(formatter, "Package {} has unparseable version {}", package, version)
+            }
+            LintIssue::InconsistentVersion { package, inconsistency } => {
+                write!(
+                    formatter,
+                    "Package {} version is inconsistent: {}",
+                    package, inconsistency
+                )
+            }
+            LintIssue::UrlDateMismatch { package, url } => {
+                write!(
+                    formatter,
+                    "Package {} has tarball {} from a different date",
+                    package, url
+                )
+            }
+        }
+    }
+}
+
+/// Checks a manifest for internal inconsistencies. Issues are returned sorted
+/// by their description.
+#[must_use]
+pub fn lint(manifest: &Manifest) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let hosts = manifest.get_hosts();
+    let packages: BTreeSet<String> = manifest.get_package_names().into_iter().collect();
+
+    for profile in manifest.get_profiles() {
+        for component in manifest.get_profile_components(&profile).unwrap_or_default() {
+            let known = hosts
+                .iter()
+                .any(|host| manifest.resolve_component_name_to_package(host, &component).is_ok());
+            if !known {
+                issues.push(LintIssue::UnknownProfileComponent {
+                    profile: profile.clone(),
+                    component,
+                });
+            }
+        }
+    }
+
+    for (from, to) in manifest.get_renames() {
+        if !packages.contains(to) {
+            issues.push(LintIssue::DanglingRename {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+
+    for host in &hosts {
+        for component in manifest.get_components(host).unwrap_or_default() {
+            let issue = match manifest.get_package(&component.package, &component.target) {
+                Err(Error::PackageUnknown(..) | Error::PackageNotTargetIndependent(..)) => {
+                    Some(LintIssue::UnknownComponentPackage {
+                        host: host.clone(),
+                        package: component.package,
+                        target: component.target,
+                    })
+                }
+                Err(Error::PackageUnavailable(..)) if !component.is_extension => {
+                    Some(LintIssue::UnavailableComponent {
+                        host: host.clone(),
+                        package: component.package,
+                        target: component.target,
+                    })
+                }
+                // Missing versions are reported per-package below
+                _ => None,
+            };
+            issues.extend(issue);
+        }
+    }
+
+    issues.extend(version_issues(manifest, &packages));
+
+    let date_directory = format!("/dist/{}/", manifest.get_date());
+    for package in &packages {
+        let availability = manifest.get_package_availability(package).unwrap_or_default();
+        for target in availability.iter().filter(|(_, available)| **available).map(|(t, _)| t) {
+            match manifest.get_package(package, target) {
+                Ok(build) => {
+                    if build.tarballs.is_empty() {
+                        issues.push(LintIssue::MissingTarball {
+                            package: package.clone(),
+                            target: target.clone(),
+                        });
+                    }
+                    for (_, binary) in build.tarballs {
+                        if !binary.url.contains(&date_directory) {
+                            issues.push(LintIssue::UrlDateMismatch {
+                                package: package.clone(),
+                                url: binary.url,
+                            });
+                        }
+                    }
+                }
+                Err(Error::MissingPackageVersion(_)) => {
+                    issues.push(LintIssue::MissingVersion {
+                        package: package.clone(),
+                    });
+                    break;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    issues.sort_by_cached_key(ToString::to_string);
+    issues.dedup();
+    issues
+}
+
+/// Checks package version strings against their git commits and the manifest
+/// date
+fn version_issues(manifest: &Manifest, packages: &BTreeSet<String>) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    // Tools developed in their own repositories (e.g. cargo) report their own
+    // commit in their version string, so commits are only compared for
+    // packages versioned with the toolchain itself
+    let toolchain_version = manifest
+        .get_package_info("rust")
+        .and_then(|info| info.parse_version().ok())
+        .map(|version| version.version);
+    for package in packages {
+        let Some(info) = manifest.get_package_info(package) else {
+            continue;
+        };
+        match info.parse_version() {
+            Ok(version) => {
+                let in_tree = toolchain_version.as_ref() == Some(&version.version);
+                for inconsistency in version.check(&info.git_commit, manifest.get_date()) {
+                    if matches!(inconsistency, VersionInconsistency::CommitMismatch { .. }) && !in_tree {
+                        continue;
+                    }
+                    issues.push(LintIssue::InconsistentVersion {
+                        package: package.clone(),
+                        inconsistency,
+                    });
+                }
+            }
+            Err(_) => issues.push(LintIssue::UnparseableVersion {
+                package: package.clone(),
+                version: info.version.clone(),
+            }),
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_value::AbbreviatedHash;
+    use crate::testing::manifest_text;
+    use crate::HashValue;
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    const VERSION: &str = "1.72.0-nightly (871b59520 2023-05-31)";
+
+    const CLIPPY_VERSION: &str = "[pkg.clippy]\nversion = \"1.72.0-nightly (871b59520 2023-05-31)\"\n";
+
+    /// Lints a manifest with `rust` and the listed packages after making the
+    /// specified replacements in its text
+    fn lint_text(packages: &[(&str, bool)], replacements: &[(&str, &str)]) -> Vec<LintIssue> {
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let mut text = manifest_text("2023-06-01", VERSION, commit, HOST, packages);
+        for (from, to) in replacements {
+            assert!(text.contains(from), "{} not in manifest", from);
+            text = text.replace(from, to);
+        }
+        lint(&Manifest::try_from(text.as_str()).unwrap())
+    }
+
+    fn host() -> Triple {
+        Triple::from_str(HOST).unwrap()
+    }
+
+    fn target() -> SupportedTarget {
+        SupportedTarget::Dependent(host())
+    }
+
+    #[test]
+    fn consistent_manifest_has_no_issues() {
+        assert_eq!(lint_text(&[("clippy", true), ("rustfmt", true)], &[]), []);
+    }
+
+    #[test]
+    fn reports_unknown_profile_components() {
+        let issues = lint_text(
+            &[("clippy", true)],
+            &[("[profiles]\n", "[profiles]\nminimal = [\"ghost\"]\n")],
+        );
+        assert_eq!(
+            issues,
+            [LintIssue::UnknownProfileComponent {
+                profile: "minimal".to_string(),
+                component: "ghost".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_dangling_renames() {
+        let issues = lint_text(
+            &[("clippy", true)],
+            &[("[renames]\n", "[renames]\nrls = { to = \"rls-preview\" }\n")],
+        );
+        assert_eq!(
+            issues,
+            [LintIssue::DanglingRename {
+                from: "rls".to_string(),
+                to: "rls-preview".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_components_without_packages() {
+        let ghost = format!("components = [{{ pkg = \"ghost\", target = \"{}\" }}, ", HOST);
+        let issues = lint_text(&[("clippy", true)], &[("components = [", &ghost)]);
+        assert_eq!(
+            issues,
+            [LintIssue::UnknownComponentPackage {
+                host: host(),
+                package: "ghost".to_string(),
+                target: target(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_unavailable_components() {
+        assert_eq!(
+            lint_text(&[("clippy", false)], &[]),
+            [LintIssue::UnavailableComponent {
+                host: host(),
+                package: "clippy".to_string(),
+                target: target(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_missing_versions() {
+        assert_eq!(
+            lint_text(&[("clippy", true)], &[(CLIPPY_VERSION, "[pkg.clippy]\n")]),
+            [LintIssue::MissingVersion {
+                package: "clippy".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_missing_tarballs() {
+        let url = format!(
+            "xz_url = \"https://static.rust-lang.org/dist/2023-06-01/clippy-{}.tar.xz\"\n",
+            HOST
+        );
+        assert_eq!(
+            lint_text(&[("clippy", true)], &[(&url, "")]),
+            [LintIssue::MissingTarball {
+                package: "clippy".to_string(),
+                target: target(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_unparseable_versions() {
+        let issues = lint_text(
+            &[("clippy", true)],
+            &[(CLIPPY_VERSION, "[pkg.clippy]\nversion = \"clippy 1.72\"\n")],
+        );
+        assert_eq!(
+            issues,
+            [LintIssue::UnparseableVersion {
+                package: "clippy".to_string(),
+                version: "clippy 1.72".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_inconsistent_versions() {
+        let version = "[pkg.clippy]\nversion = \"1.72.0-nightly (abcdef123 2023-06-02)\"\n";
+        let issues = lint_text(&[("clippy", true)], &[(CLIPPY_VERSION, version)]);
+        let git_commit = HashValue::from_str("871b5952023139738f72eba235063575062bc2e9").unwrap();
+        assert_eq!(
+            issues,
+            [
+                LintIssue::InconsistentVersion {
+                    package: "clippy".to_string(),
+                    inconsistency: VersionInconsistency::DateAfterManifest {
+                        date: NaiveDate::from_ymd_opt(2023, 6, 2).unwrap(),
+                        manifest_date: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+                    },
+                },
+                LintIssue::InconsistentVersion {
+                    package: "clippy".to_string(),
+                    inconsistency: VersionInconsistency::CommitMismatch {
+                        short: AbbreviatedHash::from_str("abcdef123").unwrap(),
+                        git_commit,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_commits_of_separately_versioned_packages() {
+        let version = "[pkg.clippy]\nversion = \"0.1.72 (abcdef123 2023-05-31)\"\n";
+        assert_eq!(lint_text(&[("clippy", true)], &[(CLIPPY_VERSION, version)]), []);
+    }
+
+    #[test]
+    fn reports_tarballs_from_other_dates() {
+        let url = format!("https://static.rust-lang.org/dist/2023-05-31/clippy-{}.tar.xz", HOST);
+        let issues = lint_text(
+            &[("clippy", true)],
+            &[("dist/2023-06-01/clippy", "dist/2023-05-31/clippy")],
+        );
+        assert_eq!(
+            issues,
+            [LintIssue::UrlDateMismatch {
+                package: "clippy".to_string(),
+                url,
+            }]
+        );
+    }
+}
@@ -1,8 +1,16 @@
 use crate::hash_value::HashValue;
+use crate::host::HostFallbacks;
+use crate::plan::{HostSubstitution, InstallPlan, MissingComponent, Provenance, Rename, Resolution, SkippedComponent};
+use crate::spec::{ComponentName, InstallSpec};
 use crate::supported_target::{SupportedTarget, TARGET_INDEPENDENT_NAME};
+use crate::toolchain::{Channel, ReleaseLookup};
+use crate::version::{PackageVersion, ParseError as VersionParseError};
 use crate::{manifest_v2, Error};
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::str::FromStr;
 use target_lexicon::Triple;
 
@@ -14,35 +22,43 @@ pub struct Manifest {
     version: String,
     date: NaiveDate,
     profiles: HashMap<String, Vec<String>>,
-    _renames: HashMap<String, String>,
+    renames: HashMap<String, String>,
     packages: HashMap<String, PackageBuilds>,
-    _components: HashMap<Triple, HashMap<(String, SupportedTarget), Component>>,
+    components: HashMap<Triple, HashMap<(String, SupportedTarget), Component>>,
     component_name_map: HashMap<Triple, HashMap<String, (String, SupportedTarget)>>,
 }
 
-/// An install specification for a Rust toolchain
-#[derive(Clone, Debug)]
-pub struct InstallSpec {
-    /// The toolchain profile
-    pub profile: String,
+/// A component which can be installed as part of a toolchain on a particular
+/// host
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Component {
+    /// The name of the package providing the component
+    pub package: String,
 
-    /// The components to be installed (in addition to those implied by the
-    /// profile)
-    pub components: HashSet<String>,
+    /// The target the package is built for
+    pub target: SupportedTarget,
 
-    /// Architectures for which the Rust standard library should be installed
-    pub targets: HashSet<String>,
+    /// Whether the component is optional (an extension) rather than part of
+    /// the toolchain by default
+    pub is_extension: bool,
 }
 
-#[derive(Clone, Debug)]
-struct Component {
-    _is_extension: bool,
+/// Versioning information for a package
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackageInfo {
+    /// The package version
+    pub version: String,
+
+    /// The git commit of the package source
+    pub git_commit: HashValue,
 }
 
-#[derive(Clone, Debug)]
-struct PackageInfo {
-    version: String,
-    git_commit: HashValue,
+impl PackageInfo {
+    /// Parses the version string into its version number, channel, commit
+    /// and date
+    pub fn parse_version(&self) -> Result<PackageVersion, VersionParseError> {
+        PackageVersion::from_str(&self.version)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -53,6 +69,17 @@ struct PackageBuilds {
 }
 
 impl PackageBuilds {
+    fn available_triples(&self) -> Vec<Triple> {
+        match &self.artifacts {
+            TargetMap::Independent(_) => Vec::new(),
+            TargetMap::Dependent(map) => map
+                .iter()
+                .filter(|(_, build)| build.is_some())
+                .map(|(triple, _)| triple.clone())
+                .collect(),
+        }
+    }
+
     fn get(&self, supported_target: &SupportedTarget) -> Result<PackageBuild, Error> {
         match &self.artifacts {
             TargetMap::Independent(build) => {
@@ -81,7 +108,7 @@ impl PackageBuilds {
 }
 
 /// A package from a Rust toolchain manifest
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Package {
     /// The package name
     pub name: String,
@@ -130,14 +157,26 @@ struct PackageBuild {
 }
 
 /// Digest types
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Digest {
     /// SHA-256
     Sha256,
 }
 
+impl Digest {
+    /// Computes the digest of the supplied data
+    #[must_use]
+    pub fn compute(self, data: &[u8]) -> HashValue {
+        match self {
+            Digest::Sha256 => HashValue::from_bytes(Sha256::digest(data).as_slice()),
+        }
+    }
+}
+
 /// Types of compression that might be applied to a remote binary
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Compression {
     /// No compression
     None,
@@ -150,7 +189,7 @@ pub enum Compression {
 }
 
 /// A binary located at some URL
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct RemoteBinary {
     /// The URL of the remote binary
     pub url: String,
@@ -159,6 +198,30 @@ pub struct RemoteBinary {
     pub digests: HashMap<Digest, HashValue>,
 }
 
+impl RemoteBinary {
+    /// Checks the supplied data against every available digest. Returns
+    /// `false` if any digest does not match or if there are no digests to
+    /// check against.
+    #[must_use]
+    pub fn verify(&self, data: &[u8]) -> bool {
+        !self.digests.is_empty()
+            && self
+                .digests
+                .iter()
+                .all(|(digest, value)| &digest.compute(data) == value)
+    }
+
+    /// Checks a local copy of the binary against its digests. Returns `None` if
+    /// the file does not exist.
+    pub fn verify_file(&self, path: &Path) -> Result<Option<bool>, Error> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(Some(self.verify(&data))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 enum TargetMap<V> {
     Independent(V),
@@ -166,6 +229,7 @@ enum TargetMap<V> {
 }
 
 impl Manifest {
+    #[allow(clippy::implicit_clone)]
     fn translate_build(from: &manifest_v2::PackageBuild) -> Option<PackageBuild> {
         if from.available {
             let mut artifacts = HashMap::new();
@@ -190,6 +254,7 @@ impl Manifest {
         }
     }
 
+    #[allow(clippy::implicit_clone)]
     fn from_v2(parsed: manifest_v2::Manifest) -> Result<Manifest, Error> {
         let mut packages = HashMap::with_capacity(parsed.packages.len());
         for (name, parsed_package) in &parsed.packages {
@@ -235,11 +300,13 @@ impl Manifest {
             let parsed_extensions = &build.extensions;
             for (parsed_components, is_extension) in [(parsed_components, false), (parsed_extensions, true)] {
                 for parsed_component in parsed_components.iter().flatten() {
+                    let package = parsed_component.package.clone();
+                    let component_target = SupportedTarget::from_str(parsed_component.target.as_str())?;
                     let component = Component {
-                        _is_extension: is_extension,
+                        package: package.clone(),
+                        target: component_target.clone(),
+                        is_extension,
                     };
-                    let package = parsed_component.package.to_string();
-                    let component_target = SupportedTarget::from_str(parsed_component.target.as_str())?;
                     target_components.insert((package, component_target), component);
                 }
             }
@@ -255,14 +322,15 @@ impl Manifest {
             version: parsed.manifest_version,
             date: parsed.date,
             profiles: parsed.profiles,
-            _renames: renames,
+            renames,
             packages,
-            _components: components,
+            components,
             component_name_map,
         };
         Ok(result)
     }
 
+    #[allow(clippy::implicit_clone)]
     fn build_component_name_map(
         components: &HashMap<Triple, HashMap<(String, SupportedTarget), Component>>,
         renames: &HashMap<String, String>,
@@ -321,6 +389,86 @@ impl Manifest {
         self.profiles.get(profile).cloned()
     }
 
+    /// The host triples for which the toolchain is available
+    #[must_use]
+    pub fn get_hosts(&self) -> Vec<Triple> {
+        self.packages
+            .get("rust")
+            .map(PackageBuilds::available_triples)
+            .unwrap_or_default()
+    }
+
+    /// The target triples for which the Rust standard library is available
+    #[must_use]
+    pub fn get_targets(&self) -> Vec<Triple> {
+        self.packages
+            .get("rust-std")
+            .map(PackageBuilds::available_triples)
+            .unwrap_or_default()
+    }
+
+    /// Returns the components which can be installed on the specified host,
+    /// including extensions such as the standard library for other targets
+    pub fn get_components(&self, host: &Triple) -> Result<Vec<Component>, Error> {
+        let components = self
+            .components
+            .get(host)
+            .ok_or_else(|| Error::UnknownTarget(host.to_string()))?;
+        Ok(components.values().cloned().collect())
+    }
+
+    /// The package renames, mapping old names to new names
+    #[must_use]
+    pub fn get_renames(&self) -> &HashMap<String, String> {
+        &self.renames
+    }
+
+    /// The names of all packages in the manifest
+    #[must_use]
+    pub fn get_package_names(&self) -> Vec<String> {
+        self.packages.keys().cloned().collect()
+    }
+
+    /// Returns the versioning information for a package. Returns `None` if the
+    /// package is unknown or the manifest contained no version information.
+    #[must_use]
+    pub fn get_package_info(&self, package: &str) -> Option<&PackageInfo> {
+        self.packages.get(package).and_then(|builds| builds.info.as_ref())
+    }
+
+    /// Returns the release the manifest is for (e.g. `1.70.0` or
+    /// `1.72.0-nightly`), taken from the version of the `rust` package.
+    /// Returns `None` if the version is missing or cannot be parsed.
+    #[must_use]
+    pub fn get_release(&self) -> Option<Channel> {
+        self.get_package_info("rust")?.parse_version().ok()?.to_channel()
+    }
+
+    /// Returns the rust-lang/rust commit the toolchain was built from, taken
+    /// from the `rustc` package (or the `rust` package if that has no
+    /// version information)
+    #[must_use]
+    pub fn get_rustc_commit(&self) -> Option<&HashValue> {
+        self.get_package_info("rustc")
+            .or_else(|| self.get_package_info("rust"))
+            .map(|info| &info.git_commit)
+    }
+
+    /// Returns whether a package is available for each target it is listed
+    /// for. Returns `None` if the package is unknown.
+    #[must_use]
+    pub fn get_package_availability(&self, package: &str) -> Option<HashMap<SupportedTarget, bool>> {
+        let builds = self.packages.get(package)?;
+        let availability = match &builds.artifacts {
+            TargetMap::Independent(build) => std::iter::once((SupportedTarget::Independent, build.is_some())).collect(),
+            TargetMap::Dependent(map) => map
+                .iter()
+                .map(|(triple, build)| (SupportedTarget::Dependent(triple.clone()), build.is_some()))
+                .collect(),
+        };
+        Some(availability)
+    }
+
     /// Given a component name and a target triple, resolves the component to
     /// package name, and the architecture that package supports. Note that
     /// due to renaming, the package name may not be the same as the
@@ -341,23 +489,70 @@ impl Manifest {
         Ok(package.clone())
     }
 
-    /// Given a target triple and a toolchain install specification, return a
-    /// list of package names and their corresponding architectures
-    /// necessary for that install.
-    pub fn find_packages_for_install(
+    /// Returns the rename which was applied when `component` resolved to
+    /// `package`, if the component referred to the package by an old name
+    /// (possibly with a target suffix)
+    fn applied_rename(&self, component: &str, package: &str, target: &SupportedTarget) -> Option<Rename> {
+        self.renames
+            .iter()
+            .find(|(from, to)| {
+                to.as_str() == package && (component == from.as_str() || component == format!("{}-{}", from, target))
+            })
+            .map(|(from, to)| Rename {
+                from: from.clone(),
+                to: to.clone(),
+            })
+    }
+
+    /// Given a target triple and a toolchain install specification, return the
+    /// packages necessary for that install, along with how each was resolved
+    /// and which profile components were skipped.
+    pub fn plan_install(&self, host: &Triple, spec: &InstallSpec) -> Result<InstallPlan, Error> {
+        self.plan_install_with(host, spec, false)
+    }
+
+    /// Like `plan_install`, but rather than failing on the first requested
+    /// component or target which is unknown on the host, or whose package is
+    /// unavailable (as is common on nightly), includes everything that can be
+    /// installed and lists the rest in `InstallPlan::missing`.
+    pub fn plan_install_available(&self, host: &Triple, spec: &InstallSpec) -> Result<InstallPlan, Error> {
+        let mut plan = self.plan_install_with(host, spec, true)?;
+        plan.remove_unavailable(self)?;
+        Ok(plan)
+    }
+
+    /// Like `plan_install`, but packages built for the host which are
+    /// unavailable are replaced with builds for a compatible host from
+    /// `fallbacks` where possible (e.g. an `x86_64-apple-darwin` build under
+    /// Rosetta). Substitutions are listed in `InstallPlan::substituted`.
+    pub fn plan_install_with_fallbacks(
         &self,
         host: &Triple,
         spec: &InstallSpec,
-    ) -> Result<HashSet<(String, SupportedTarget)>, Error> {
-        let mut result = HashSet::new();
+        fallbacks: &HostFallbacks,
+    ) -> Result<InstallPlan, Error> {
+        let mut plan = self.plan_install_with(host, spec, false)?;
+        plan.substitute_hosts(self, host, fallbacks)?;
+        Ok(plan)
+    }
+
+    fn plan_install_with(&self, host: &Triple, spec: &InstallSpec, skip_missing: bool) -> Result<InstallPlan, Error> {
+        let mut plan = InstallPlan::default();
         let profile_components = self
             .profiles
             .get(&spec.profile)
-            .ok_or_else(|| Error::UnknownProfile(spec.profile.to_string()))?;
+            .ok_or_else(|| Error::UnknownProfile(spec.profile.clone()))?;
         for component in profile_components {
             match self.resolve_component_name_to_package(host, component) {
-                Ok(package) => {
-                    result.insert(package);
+                Ok((package, target)) => {
+                    let resolution = Resolution {
+                        provenance: Provenance::Profile {
+                            profile: spec.profile.clone(),
+                            component: component.clone(),
+                        },
+                        rename: self.applied_rename(component, &package, &target),
+                    };
+                    plan.insert(package, target, resolution);
                 }
                 Err(Error::PackageUnknown(..)) => {
                     // Since profiles can apparently contain components that
@@ -365,50 +560,157 @@ impl Manifest {
                     // (e.g. rust-mingw), it is presumably
                     // safe to ignore this, given that the profile component
                     // list isn't supplied by the user.
+                    plan.skip(SkippedComponent {
+                        profile: spec.profile.clone(),
+                        component: component.clone(),
+                    });
                 }
                 Err(e) => return Err(e),
             }
         }
+        let mut requested = Vec::with_capacity(spec.components.len() + spec.targets.len());
         for component in &spec.components {
-            let package = self.resolve_component_name_to_package(host, component)?;
-            result.insert(package);
+            let provenance = Provenance::Component {
+                component: component.to_string(),
+            };
+            requested.push((component.to_string(), provenance));
         }
         for target in &spec.targets {
-            let target = Triple::from_str(target)?;
-            let component = format!("{}-{}", "rust-std", target);
-            let package = self.resolve_component_name_to_package(host, &component)?;
-            result.insert(package);
+            let provenance = Provenance::Target {
+                target: target.to_string(),
+            };
+            let component = ComponentName::for_target("rust-std", target.clone());
+            requested.push((component.to_string(), provenance));
         }
-        Ok(result)
+        for (component, provenance) in requested {
+            match self.resolve_component_name_to_package(host, &component) {
+                Ok((package, target)) => {
+                    let rename = self.applied_rename(&component, &package, &target);
+                    plan.insert(package, target, Resolution { provenance, rename });
+                }
+                Err(Error::PackageUnknown(..)) if skip_missing => {
+                    plan.add_missing(MissingComponent::Unknown { provenance });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Given a target triple and a toolchain install specification, return a
+    /// list of package names and their corresponding architectures
+    /// necessary for that install.
+    pub fn find_packages_for_install(
+        &self,
+        host: &Triple,
+        spec: &InstallSpec,
+    ) -> Result<HashSet<(String, SupportedTarget)>, Error> {
+        Ok(self.plan_install(host, spec)?.packages())
     }
 
     /// Given a target triple and a toolchain install specification, return a
     /// list of package descriptions which includes information about the
     /// archives which need to be downloaded.
     pub fn find_downloads_for_install(&self, host: &Triple, spec: &InstallSpec) -> Result<Vec<Package>, Error> {
+        self.plan_install(host, spec)?.to_downloads(self)
+    }
+
+    /// Like `find_downloads_for_install`, but substitutes builds for
+    /// compatible hosts from `fallbacks` where the host's own builds are
+    /// unavailable, returning the substitutions alongside the packages.
+    pub fn find_downloads_for_install_with_fallbacks(
+        &self,
+        host: &Triple,
+        spec: &InstallSpec,
+        fallbacks: &HostFallbacks,
+    ) -> Result<(Vec<Package>, Vec<HostSubstitution>), Error> {
+        let plan = self.plan_install_with_fallbacks(host, spec, fallbacks)?;
+        Ok((plan.to_downloads(self)?, plan.substituted().to_vec()))
+    }
+
+    /// Like `find_downloads_for_install`, but skips requested components and
+    /// targets which are unknown or unavailable, returning them alongside the
+    /// packages which can be downloaded.
+    pub fn find_available_downloads_for_install(
+        &self,
+        host: &Triple,
+        spec: &InstallSpec,
+    ) -> Result<(Vec<Package>, Vec<MissingComponent>), Error> {
+        let plan = self.plan_install_available(host, spec)?;
+        Ok((plan.to_downloads(self)?, plan.missing().to_vec()))
+    }
+
+    /// Returns every available package which is either target-independent or
+    /// built for one of the specified target triples. This is useful when
+    /// mirroring everything that might be needed for a set of hosts and
+    /// targets rather than a single install specification.
+    pub fn find_downloads_for_targets(&self, targets: &HashSet<Triple>) -> Result<Vec<Package>, Error> {
+        self.find_downloads_matching(|triple| targets.contains(triple))
+    }
+
+    /// Returns every available package in the manifest
+    pub fn find_all_downloads(&self) -> Result<Vec<Package>, Error> {
+        self.find_downloads_matching(|_| true)
+    }
+
+    fn find_downloads_matching<P: Fn(&Triple) -> bool>(&self, predicate: P) -> Result<Vec<Package>, Error> {
         let mut result = Vec::new();
-        let packages = self.find_packages_for_install(host, spec)?;
-        for (package_name, target) in &packages {
-            let builds = self
-                .packages
-                .get(package_name)
-                .ok_or_else(|| Error::PackageUnknown(package_name.to_string(), target.clone()))?;
-            let build = builds.get(target)?;
-            let info = builds
-                .info
-                .as_ref()
-                .ok_or_else(|| Error::MissingPackageVersion(package_name.clone()))?;
-            let package = Package {
-                name: package_name.clone(),
-                version: info.version.clone(),
-                git_commit: info.git_commit.clone(),
-                supported_target: target.clone(),
-                tarballs: build.artifacts.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        for (package_name, builds) in &self.packages {
+            let supported_targets: Vec<SupportedTarget> = match &builds.artifacts {
+                TargetMap::Independent(Some(_)) => vec![SupportedTarget::Independent],
+                TargetMap::Independent(None) => Vec::new(),
+                TargetMap::Dependent(map) => map
+                    .iter()
+                    .filter(|(triple, build)| build.is_some() && predicate(triple))
+                    .map(|(triple, _)| SupportedTarget::Dependent(triple.clone()))
+                    .collect(),
             };
-            result.push(package);
+            for supported_target in &supported_targets {
+                result.push(self.get_package(package_name, supported_target)?);
+            }
         }
         Ok(result)
     }
+
+    /// Returns the description of a package build, including the archives
+    /// which need to be downloaded.
+    pub fn get_package(&self, package_name: &str, target: &SupportedTarget) -> Result<Package, Error> {
+        let builds = self
+            .packages
+            .get(package_name)
+            .ok_or_else(|| Error::PackageUnknown(package_name.to_string(), target.clone()))?;
+        let build = builds.get(target)?;
+        let info = builds
+            .info
+            .as_ref()
+            .ok_or_else(|| Error::MissingPackageVersion(package_name.to_string()))?;
+        let package = Package {
+            name: package_name.to_string(),
+            version: info.version.clone(),
+            git_commit: info.git_commit.clone(),
+            supported_target: target.clone(),
+            tarballs: build.artifacts.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        };
+        Ok(package)
+    }
+}
+
+/// A manifest knows the version its own channel was on at its date, taken
+/// from the `rust` package
+impl ReleaseLookup for Manifest {
+    fn resolve(&self, channel: &Channel, date: NaiveDate) -> Option<Channel> {
+        if date != self.get_date() {
+            return None;
+        }
+        let resolved = self.get_release()?;
+        let matches = match channel {
+            Channel::Stable => matches!(resolved, Channel::Version(..)),
+            Channel::Beta => matches!(resolved, Channel::VersionBeta { .. }),
+            Channel::Nightly => matches!(resolved, Channel::VersionNightly { .. }),
+            _ => false,
+        };
+        matches.then_some(resolved)
+    }
 }
 
 impl TryFrom<&str> for Manifest {
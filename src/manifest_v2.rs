@@ -4,6 +4,7 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[allow(clippy::struct_field_names)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Manifest {
     #[serde(rename = "manifest-version")]
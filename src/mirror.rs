@@ -0,0 +1,785 @@
+use crate::fetch::{dist_path, local_path, Fetcher, ManifestFetcher, DEFAULT_DIST_SERVER};
+use crate::manifest::{Digest, Package};
+use crate::toolchain::Channel;
+use crate::{Error, HashValue, InstallSpec, Manifest, Toolchain};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use target_lexicon::Triple;
+
+/// The packages to mirror for a toolchain
+#[derive(Clone, Debug)]
+pub enum MirrorSelection {
+    /// The packages required to install a specification on each of the listed
+    /// hosts
+    Install {
+        /// The hosts the toolchain will be installed on
+        hosts: Vec<Triple>,
+
+        /// The install specification
+        spec: InstallSpec,
+    },
+
+    /// Every available package built for one of the listed hosts or targets,
+    /// along with all target-independent packages
+    Everything {
+        /// The host and target triples to mirror packages for
+        targets: Vec<Triple>,
+    },
+}
+
+/// A toolchain to mirror, along with the packages to include
+#[derive(Clone, Debug)]
+pub struct MirrorEntry {
+    /// The toolchain
+    pub toolchain: Toolchain,
+
+    /// The packages of the toolchain to mirror
+    pub selection: MirrorSelection,
+}
+
+/// The files written or left unchanged by a mirror update
+#[derive(Clone, Debug, Default)]
+pub struct MirrorReport {
+    /// Manifests written to the mirror
+    pub manifests: Vec<PathBuf>,
+
+    /// Tarballs downloaded into the mirror
+    pub downloaded: Vec<PathBuf>,
+
+    /// Tarballs already present in the mirror with the correct digest
+    pub skipped: Vec<PathBuf>,
+}
+
+/// A static mirror of a Rust distribution server stored in a local directory.
+///
+/// Files are stored under the same paths used by `static.rust-lang.org`, so
+/// the directory can be served as-is and used as `RUSTUP_DIST_SERVER`.
+#[derive(Clone, Debug)]
+pub struct Mirror {
+    root: PathBuf,
+    base_url: Option<String>,
+}
+
+impl Mirror {
+    /// Constructs a mirror rooted at the specified directory
+    pub fn new<P: Into<PathBuf>>(root: P) -> Mirror {
+        Mirror {
+            root: root.into(),
+            base_url: None,
+        }
+    }
+
+    /// Rewrites the URLs in mirrored manifests to refer to the specified base
+    /// URL rather than the official distribution server
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: &str) -> Mirror {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The directory containing the mirror
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    /// Returns the local path of a remote file referred to by a manifest. URLs
+    /// may refer to either the official distribution server or the base URL
+    /// of this mirror.
+    pub fn path_for_url(&self, url: &str) -> Result<PathBuf, Error> {
+        let relative = self
+            .base_url
+            .as_deref()
+            .and_then(|base_url| dist_path(url, base_url).ok())
+            .map_or_else(|| dist_path(url, DEFAULT_DIST_SERVER), Ok)?;
+        local_path(&self.root, relative)
+            .ok_or_else(|| Error::UnexpectedServer(url.to_string(), DEFAULT_DIST_SERVER.to_string()))
+    }
+
+    /// Returns the manifests stored in the mirror
+    pub fn stored_manifests(&self) -> Result<Vec<StoredManifest>, Error> {
+        let dist = self.root.join("dist");
+        let mut result = Vec::new();
+        for path in files_under(&dist)? {
+            let Some(channel) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("channel-rust-"))
+                .and_then(|n| n.strip_suffix(".toml"))
+                .and_then(|n| Channel::from_str(n).or_else(|_| Channel::custom(n)).ok())
+            else {
+                continue;
+            };
+            let date = if path.parent() == Some(dist.as_path()) {
+                None
+            } else {
+                let dir_name = path.parent().and_then(Path::file_name).and_then(|n| n.to_str());
+                match dir_name.map(NaiveDate::from_str) {
+                    Some(Ok(date)) => Some(date),
+                    _ => continue,
+                }
+            };
+            result.push(StoredManifest { path, channel, date });
+        }
+        result.sort_by(|a, b| (a.date, &a.path).cmp(&(b.date, &b.path)));
+        Ok(result)
+    }
+
+    /// Returns the stable releases for which a manifest is stored, whether
+    /// under a version (e.g. `channel-rust-1.70.toml`) or as a stable
+    /// manifest, in ascending order of version. Manifests which cannot be
+    /// parsed are ignored.
+    pub fn stable_releases(&self) -> Result<Vec<StableRelease>, Error> {
+        let mut releases = BTreeMap::new();
+        for stored in self.stored_manifests()? {
+            if !matches!(stored.channel, Channel::Stable | Channel::Version(..)) {
+                continue;
+            }
+            let text = std::fs::read_to_string(&stored.path)?;
+            let Ok(manifest) = Manifest::try_from(text.as_str()) else {
+                continue;
+            };
+            if let Some(Channel::Version(major, minor, Some(patch))) = manifest.get_release() {
+                releases.entry((major, minor, patch)).or_insert_with(|| StableRelease {
+                    version: Channel::Version(major, minor, Some(patch)),
+                    date: manifest.get_date(),
+                });
+            }
+        }
+        Ok(releases.into_values().collect())
+    }
+
+    /// Checks every stored manifest and the tarballs it refers to. If a
+    /// selection is supplied, only tarballs within the selection are expected
+    /// to be present; otherwise every tarball listed in each manifest is. A
+    /// tarball listed by several manifests is reported once, as missing if
+    /// any of them expects it.
+    pub fn audit(&self, selection: Option<&MirrorSelection>) -> Result<AuditReport, Error> {
+        let mut report = AuditReport::default();
+        let mut referenced = HashSet::new();
+        let mut checked = HashMap::new();
+        let mut reported = HashSet::new();
+        for stored in self.stored_manifests()? {
+            let text = std::fs::read(&stored.path)?;
+            referenced.insert(stored.path.clone());
+            if !Self::check_digest_file(&stored.path, &text)? {
+                report.corrupt.push(stored.path.clone());
+            }
+            let Ok(Ok(manifest)) = std::str::from_utf8(&text).map(Manifest::try_from) else {
+                report.unparseable.push(stored.path.clone());
+                continue;
+            };
+            report.manifests.push(stored.path.clone());
+            let mut expected = HashSet::new();
+            if let Some(selection) = selection {
+                for package in Self::select_packages(&manifest, selection)? {
+                    expected.extend(package.tarballs.iter().map(|(_, binary)| binary.url.clone()));
+                }
+            }
+            for package in manifest.find_all_downloads()? {
+                for (_, binary) in &package.tarballs {
+                    let path = self.path_for_url(&binary.url)?;
+                    referenced.insert(path.clone());
+                    let status = match checked.get(&path) {
+                        Some(status) => *status,
+                        None => *checked.entry(path.clone()).or_insert(binary.verify_file(&path)?),
+                    };
+                    let is_expected = selection.is_none() || expected.contains(&binary.url);
+                    if (status.is_none() && !is_expected) || !reported.insert(path.clone()) {
+                        continue;
+                    }
+                    match status {
+                        Some(true) => report.verified.push(path),
+                        Some(false) => report.corrupt.push(path),
+                        None => report.missing.push(path),
+                    }
+                }
+            }
+        }
+        report.orphaned = self.unreferenced_files(&referenced)?;
+        Ok(report)
+    }
+
+    /// Removes manifests not retained by the policy, then every tarball not
+    /// referenced by a retained manifest. Manifests without a date in their
+    /// path (the latest release of a channel) are always retained. A retained
+    /// manifest which cannot be parsed is kept and reported, but the tarballs
+    /// it refers to cannot be determined, so are only kept if another retained
+    /// manifest refers to them. If `dry_run` is set, the files that would be
+    /// removed are reported but not deleted.
+    pub fn prune(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<PruneReport, Error> {
+        let stored = self.stored_manifests()?;
+        let mut dates_by_kind: HashMap<ChannelKind, BTreeSet<NaiveDate>> = HashMap::new();
+        for manifest in &stored {
+            if let Some(date) = manifest.date {
+                dates_by_kind
+                    .entry(ChannelKind::of(&manifest.channel))
+                    .or_default()
+                    .insert(date);
+            }
+        }
+        let retained_dates: HashMap<ChannelKind, HashSet<NaiveDate>> = dates_by_kind
+            .into_iter()
+            .map(|(kind, dates)| {
+                let limit = policy.limit(kind).unwrap_or(dates.len());
+                (kind, dates.into_iter().rev().take(limit).collect())
+            })
+            .collect();
+        let mut report = PruneReport::default();
+        let mut referenced = HashSet::new();
+        for manifest in &stored {
+            let retained = match manifest.date {
+                None => true,
+                Some(date) => retained_dates
+                    .get(&ChannelKind::of(&manifest.channel))
+                    .is_some_and(|dates| dates.contains(&date)),
+            };
+            if !retained {
+                continue;
+            }
+            referenced.insert(manifest.path.clone());
+            let text = std::fs::read(&manifest.path)?;
+            let Ok(Ok(parsed)) = std::str::from_utf8(&text).map(Manifest::try_from) else {
+                report.unparseable.push(manifest.path.clone());
+                continue;
+            };
+            for package in parsed.find_all_downloads()? {
+                for (_, binary) in &package.tarballs {
+                    referenced.insert(self.path_for_url(&binary.url)?);
+                }
+            }
+        }
+        report.removed = self.unreferenced_files(&referenced)?;
+        if !dry_run {
+            for path in &report.removed {
+                std::fs::remove_file(path)?;
+            }
+            for dir in report
+                .removed
+                .iter()
+                .filter_map(|p| p.parent())
+                .collect::<BTreeSet<_>>()
+            {
+                if std::fs::read_dir(dir)?.next().is_none() {
+                    std::fs::remove_dir(dir)?;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns every file beneath `dist` which is neither in `referenced` nor
+    /// the `.sha256` companion of a referenced file
+    fn unreferenced_files(&self, referenced: &HashSet<PathBuf>) -> Result<Vec<PathBuf>, Error> {
+        let mut result = Vec::new();
+        for path in files_under(&self.root.join("dist"))? {
+            let companion_of = path.to_str().and_then(|p| p.strip_suffix(".sha256")).map(PathBuf::from);
+            let is_referenced =
+                referenced.contains(&path) || companion_of.is_some_and(|original| referenced.contains(&original));
+            if !is_referenced {
+                result.push(path);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Checks a file against its `.sha256` companion. A missing companion is
+    /// treated as a mismatch.
+    fn check_digest_file(path: &Path, data: &[u8]) -> Result<bool, Error> {
+        let mut digest_path = path.as_os_str().to_owned();
+        digest_path.push(".sha256");
+        let contents = match std::fs::read_to_string(digest_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(matches_digest_file(&contents, data))
+    }
+
+    /// Fetches the manifests and tarballs for the specified toolchains into the
+    /// mirror. Manifests are checked against their published `.sha256` files
+    /// before use. Tarballs which are already present with the expected digest
+    /// are not downloaded again.
+    pub fn update<F: Fetcher>(
+        &self,
+        fetcher: &ManifestFetcher<F>,
+        entries: &[MirrorEntry],
+    ) -> Result<MirrorReport, Error> {
+        let mut report = MirrorReport::default();
+        let mut visited = HashSet::new();
+        for entry in entries {
+            let url = entry.toolchain.manifest_url_for_server(fetcher.dist_server());
+            let text = fetcher
+                .fetch_verified_manifest_text(&entry.toolchain)?
+                .ok_or(Error::NotFound(url))?;
+            let manifest = Manifest::try_from(text.as_str())?;
+            for package in Self::select_packages(&manifest, &entry.selection)? {
+                for (_, binary) in &package.tarballs {
+                    let path = self.path_for_url(&binary.url)?;
+                    if !visited.insert(path.clone()) {
+                        continue;
+                    }
+                    if binary.verify_file(&path)? == Some(true) {
+                        report.skipped.push(path);
+                    } else {
+                        let data = fetcher.fetch_binary(binary)?;
+                        write_with_digest(&path, &data)?;
+                        report.downloaded.push(path);
+                    }
+                }
+            }
+            let text = match &self.base_url {
+                Some(base_url) => rewrite_manifest_urls(&text, DEFAULT_DIST_SERVER, base_url),
+                None => text,
+            };
+            let dated = Toolchain {
+                date: Some(manifest.get_date()),
+                ..entry.toolchain.clone()
+            };
+            let mut manifest_paths = vec![dated.manifest_path()];
+            if entry.toolchain.date.is_none() {
+                manifest_paths.push(entry.toolchain.manifest_path());
+            }
+            for manifest_path in manifest_paths {
+                let path = self.root.join(manifest_path);
+                write_with_digest(&path, text.as_bytes())?;
+                report.manifests.push(path);
+            }
+        }
+        Ok(report)
+    }
+
+    fn select_packages(manifest: &Manifest, selection: &MirrorSelection) -> Result<Vec<Package>, Error> {
+        match selection {
+            MirrorSelection::Install { hosts, spec } => {
+                let mut packages = Vec::new();
+                for host in hosts {
+                    packages.extend(manifest.find_downloads_for_install(host, spec)?);
+                }
+                Ok(packages)
+            }
+            MirrorSelection::Everything { targets } => {
+                manifest.find_downloads_for_targets(&targets.iter().cloned().collect())
+            }
+        }
+    }
+}
+
+/// A manifest stored in a mirror
+#[derive(Clone, Debug)]
+pub struct StoredManifest {
+    /// The location of the manifest
+    pub path: PathBuf,
+
+    /// The channel the manifest is for
+    pub channel: Channel,
+
+    /// The date in the manifest path, or `None` if this is the latest manifest
+    /// for the channel
+    pub date: Option<NaiveDate>,
+}
+
+/// A stable release known from a stored manifest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StableRelease {
+    /// The full version of the release (e.g. `1.70.0`)
+    pub version: Channel,
+
+    /// The manifest date, which is the release date
+    pub date: NaiveDate,
+}
+
+/// The outcome of checking the contents of a mirror
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    /// Manifests which were successfully parsed
+    pub manifests: Vec<PathBuf>,
+
+    /// Manifests which could not be parsed
+    pub unparseable: Vec<PathBuf>,
+
+    /// Tarballs which matched their expected digest
+    pub verified: Vec<PathBuf>,
+
+    /// Tarballs referenced by a manifest which were not present
+    pub missing: Vec<PathBuf>,
+
+    /// Tarballs and manifests which did not match their expected digest
+    pub corrupt: Vec<PathBuf>,
+
+    /// Files which are not referenced by any manifest
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl AuditReport {
+    /// Returns `true` if no problems were found
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.unparseable.is_empty() && self.missing.is_empty() && self.corrupt.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// The outcome of pruning a mirror
+#[derive(Clone, Debug, Default)]
+pub struct PruneReport {
+    /// Files which were removed (or would have been, for a dry run)
+    pub removed: Vec<PathBuf>,
+
+    /// Retained manifests which could not be parsed
+    pub unparseable: Vec<PathBuf>,
+}
+
+/// How many dated manifests of each kind of channel to retain when pruning a
+/// mirror. `None` retains all of them. Manifests for custom channels are
+/// always retained.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetentionPolicy {
+    /// The number of nightly manifests to retain (including those for specific
+    /// versions)
+    pub nightly: Option<usize>,
+
+    /// The number of beta manifests to retain (including those for specific
+    /// versions)
+    pub beta: Option<usize>,
+
+    /// The number of stable manifests to retain (including those for specific
+    /// versions)
+    pub stable: Option<usize>,
+}
+
+impl RetentionPolicy {
+    fn limit(&self, kind: ChannelKind) -> Option<usize> {
+        match kind {
+            ChannelKind::Nightly => self.nightly,
+            ChannelKind::Beta => self.beta,
+            ChannelKind::Stable => self.stable,
+            ChannelKind::Custom => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum ChannelKind {
+    Nightly,
+    Beta,
+    Stable,
+    Custom,
+}
+
+impl ChannelKind {
+    fn of(channel: &Channel) -> ChannelKind {
+        match channel {
+            Channel::Nightly | Channel::VersionNightly { .. } => ChannelKind::Nightly,
+            Channel::Beta | Channel::VersionBeta { .. } => ChannelKind::Beta,
+            Channel::Stable | Channel::Version(..) => ChannelKind::Stable,
+            Channel::Custom(_) => ChannelKind::Custom,
+        }
+    }
+}
+
+/// Lists all files beneath a directory. Returns an empty list if the directory
+/// does not exist.
+pub(crate) fn files_under(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut result = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                result.push(entry.path());
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Replaces references to one distribution server in manifest text with
+/// another
+#[must_use]
+pub fn rewrite_manifest_urls(text: &str, from: &str, to: &str) -> String {
+    let from = format!("\"{}/", from.trim_end_matches('/'));
+    let to = format!("\"{}/", to.trim_end_matches('/'));
+    text.replace(&from, &to)
+}
+
+/// Returns the contents of the `.sha256` file published alongside a file
+#[must_use]
+pub fn sha256_file_contents(data: &[u8], file_name: &str) -> String {
+    format!("{}  {}\n", Digest::Sha256.compute(data), file_name)
+}
+
+/// Checks data against the contents of a `.sha256` file
+pub(crate) fn matches_digest_file(contents: &str, data: &[u8]) -> bool {
+    let expected = contents.split_whitespace().next().map(HashValue::from_str);
+    matches!(expected, Some(Ok(expected)) if expected == Digest::Sha256.compute(data))
+}
+
+/// Distinguishes the temporary files of concurrent writers within a process
+static PARTIAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The paths being written by `write_with_digest`, so that concurrent writers
+/// of the same file take turns
+static WRITING: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Signalled when a path is removed from `WRITING`
+static WRITTEN: Condvar = Condvar::new();
+
+/// Holds a path in `WRITING` until dropped
+struct WriteLock<'a> {
+    path: &'a Path,
+}
+
+impl<'a> WriteLock<'a> {
+    fn acquire(path: &'a Path) -> WriteLock<'a> {
+        let mut writing = WRITING.lock().unwrap_or_else(PoisonError::into_inner);
+        while writing.contains(path) {
+            writing = WRITTEN.wait(writing).unwrap_or_else(PoisonError::into_inner);
+        }
+        writing.insert(path.to_path_buf());
+        WriteLock { path }
+    }
+}
+
+impl Drop for WriteLock<'_> {
+    fn drop(&mut self) {
+        WRITING.lock().unwrap_or_else(PoisonError::into_inner).remove(self.path);
+        WRITTEN.notify_all();
+    }
+}
+
+/// Writes a file and its `.sha256` companion, creating parent directories as
+/// required. Each file is written to a temporary name unique to the writer
+/// and then renamed into place, so that interrupted or concurrent writes
+/// never leave truncated files in the mirror. Writers of the same path within
+/// a process take turns, and the digest is renamed into place before the
+/// data, so the pair is left consistent.
+pub(crate) fn write_with_digest(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid path: {}", path.display()),
+        ))
+    })?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let digest = sha256_file_contents(data, file_name);
+    let _lock = WriteLock::acquire(path);
+    for (path, data) in [
+        (path.with_file_name(format!("{}.sha256", file_name)), digest.as_bytes()),
+        (path.to_path_buf(), data),
+    ] {
+        let partial = path.with_file_name(format!(
+            ".{}.{}-{}.partial",
+            path.file_name().and_then(|n| n.to_str()).unwrap_or_default(),
+            std::process::id(),
+            PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = std::fs::write(&partial, data).and_then(|()| std::fs::rename(&partial, &path));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::DirectoryFetcher;
+    use crate::testing::{manifest_text, publish, publish_manifest, temp_dir, TARBALL};
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    /// Publishes nightlies for the listed days of June 2023 with `clippy`
+    /// available, the last of which is also the latest nightly
+    fn upstream(root: &Path, days: &[u32]) -> ManifestFetcher<DirectoryFetcher> {
+        for (index, day) in days.iter().enumerate() {
+            let date = format!("2023-06-{:02}", day);
+            let commit = "871b5952023139738f72eba235063575062bc2e9";
+            let text = manifest_text(&date, "1.72.0-nightly", commit, HOST, &[("clippy", true)]);
+            publish_manifest(root, "nightly", &text, index + 1 == days.len());
+        }
+        ManifestFetcher::new(DirectoryFetcher::new(root, DEFAULT_DIST_SERVER))
+    }
+
+    fn nightly(date: Option<&str>) -> MirrorEntry {
+        MirrorEntry {
+            toolchain: Toolchain {
+                channel: Channel::Nightly,
+                date: date.map(|date| NaiveDate::from_str(date).unwrap()),
+                host: None,
+            },
+            selection: MirrorSelection::Everything {
+                targets: vec![Triple::from_str(HOST).unwrap()],
+            },
+        }
+    }
+
+    fn tarball(mirror: &Mirror, date: &str, package: &str) -> PathBuf {
+        mirror.root().join(format!("dist/{}/{}-{}.tar.xz", date, package, HOST))
+    }
+
+    #[test]
+    fn update_downloads_missing_tarballs() {
+        let dir = temp_dir("mirror-update");
+        let fetcher = upstream(&dir.join("upstream"), &[1]);
+        let mirror = Mirror::new(dir.join("mirror"));
+        let report = mirror.update(&fetcher, &[nightly(None)]).unwrap();
+        assert_eq!(report.downloaded.len(), 2);
+        assert_eq!(
+            report.manifests,
+            [
+                mirror.root().join("dist/2023-06-01/channel-rust-nightly.toml"),
+                mirror.root().join("dist/channel-rust-nightly.toml")
+            ]
+        );
+        assert_eq!(
+            std::fs::read(tarball(&mirror, "2023-06-01", "clippy")).unwrap(),
+            TARBALL
+        );
+
+        std::fs::write(tarball(&mirror, "2023-06-01", "clippy"), b"truncated").unwrap();
+        let report = mirror.update(&fetcher, &[nightly(None)]).unwrap();
+        assert_eq!(report.downloaded, [tarball(&mirror, "2023-06-01", "clippy")]);
+        assert_eq!(report.skipped, [tarball(&mirror, "2023-06-01", "rust")]);
+        assert!(mirror.audit(None).unwrap().is_healthy());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn update_verifies_manifests() {
+        let dir = temp_dir("mirror-verify");
+        let upstream_root = dir.join("upstream");
+        let fetcher = upstream(&upstream_root, &[1]);
+        let mirror = Mirror::new(dir.join("mirror"));
+        let digest_path = upstream_root.join("dist/channel-rust-nightly.toml.sha256");
+        std::fs::write(
+            &digest_path,
+            sha256_file_contents(b"other", "channel-rust-nightly.toml"),
+        )
+        .unwrap();
+        assert!(matches!(
+            mirror.update(&fetcher, &[nightly(None)]),
+            Err(Error::DigestMismatch(_))
+        ));
+        std::fs::remove_file(&digest_path).unwrap();
+        assert!(matches!(
+            mirror.update(&fetcher, &[nightly(None)]),
+            Err(Error::NotFound(_))
+        ));
+        assert!(!mirror.root().join("dist").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn audit_reports_problems() {
+        let dir = temp_dir("mirror-audit");
+        let fetcher = upstream(&dir.join("upstream"), &[1]);
+        let mirror = Mirror::new(dir.join("mirror"));
+        mirror.update(&fetcher, &[nightly(None)]).unwrap();
+        std::fs::remove_file(tarball(&mirror, "2023-06-01", "rust")).unwrap();
+        std::fs::write(tarball(&mirror, "2023-06-01", "clippy"), b"corrupt").unwrap();
+        let stray = mirror.root().join("dist/2023-06-01/stray.tar.xz");
+        std::fs::write(&stray, TARBALL).unwrap();
+        let unparseable = "dist/2023-06-02/channel-rust-nightly.toml";
+        publish(mirror.root(), unparseable, b"not a manifest");
+
+        let report = mirror.audit(None).unwrap();
+        assert!(!report.is_healthy());
+        assert_eq!(report.manifests.len(), 2);
+        assert_eq!(report.unparseable, [mirror.root().join(unparseable)]);
+        assert_eq!(report.missing, [tarball(&mirror, "2023-06-01", "rust")]);
+        assert_eq!(report.corrupt, [tarball(&mirror, "2023-06-01", "clippy")]);
+        assert!(report.verified.is_empty());
+        assert_eq!(report.orphaned, [stray]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_applies_retention_policy() {
+        let dir = temp_dir("mirror-prune");
+        let fetcher = upstream(&dir.join("upstream"), &[1, 2, 3]);
+        let mirror = Mirror::new(dir.join("mirror"));
+        let entries = [nightly(Some("2023-06-01")), nightly(Some("2023-06-02")), nightly(None)];
+        mirror.update(&fetcher, &entries).unwrap();
+        let unparseable = mirror.root().join("dist/2023-06-04/channel-rust-nightly.toml");
+        publish(
+            mirror.root(),
+            "dist/2023-06-04/channel-rust-nightly.toml",
+            b"not a manifest",
+        );
+
+        let policy = RetentionPolicy {
+            nightly: Some(2),
+            ..RetentionPolicy::default()
+        };
+        let dry_run = mirror.prune(&policy, true).unwrap();
+        assert_eq!(dry_run.unparseable, [unparseable]);
+        assert!(mirror.root().join("dist/2023-06-01").is_dir());
+        let report = mirror.prune(&policy, false).unwrap();
+        assert_eq!(report.removed, dry_run.removed);
+        let removed: Vec<String> = report
+            .removed
+            .iter()
+            .map(|path| path.strip_prefix(mirror.root()).unwrap().display().to_string())
+            .collect();
+        assert_eq!(
+            removed,
+            [
+                "dist/2023-06-01/channel-rust-nightly.toml",
+                "dist/2023-06-01/channel-rust-nightly.toml.sha256",
+                "dist/2023-06-01/clippy-x86_64-unknown-linux-gnu.tar.xz",
+                "dist/2023-06-01/clippy-x86_64-unknown-linux-gnu.tar.xz.sha256",
+                "dist/2023-06-01/rust-x86_64-unknown-linux-gnu.tar.xz",
+                "dist/2023-06-01/rust-x86_64-unknown-linux-gnu.tar.xz.sha256",
+                "dist/2023-06-02/channel-rust-nightly.toml",
+                "dist/2023-06-02/channel-rust-nightly.toml.sha256",
+                "dist/2023-06-02/clippy-x86_64-unknown-linux-gnu.tar.xz",
+                "dist/2023-06-02/clippy-x86_64-unknown-linux-gnu.tar.xz.sha256",
+                "dist/2023-06-02/rust-x86_64-unknown-linux-gnu.tar.xz",
+                "dist/2023-06-02/rust-x86_64-unknown-linux-gnu.tar.xz.sha256",
+            ]
+        );
+        assert!(!mirror.root().join("dist/2023-06-01").exists());
+        assert!(tarball(&mirror, "2023-06-03", "rust").is_file());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writes_do_not_interfere() {
+        let dir = std::env::temp_dir().join(format!("rtm-mirror-test-{}", std::process::id()));
+        let path = dir.join("dist").join("artefact.tar.xz");
+        let contents: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 256 * 1024]).collect();
+        std::thread::scope(|scope| {
+            for data in &contents {
+                let path = &path;
+                scope.spawn(move || write_with_digest(path, data).unwrap());
+            }
+        });
+        let written = std::fs::read(&path).unwrap();
+        assert!(contents.contains(&written));
+        let digest = std::fs::read_to_string(dir.join("dist").join("artefact.tar.xz.sha256")).unwrap();
+        assert!(matches_digest_file(&digest, &written));
+        let leftovers: Vec<PathBuf> = files_under(&dir)
+            .unwrap()
+            .into_iter()
+            .filter(|p| p.to_string_lossy().ends_with(".partial"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use crate::fetch::{Fetcher, ManifestFetcher};
+use crate::toolchain::Channel;
+use crate::{Error, InstallPlan, InstallSpec, Toolchain};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use target_lexicon::Triple;
+
+/// A `rust-version` declared by a package in a workspace
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeclaredRustVersion {
+    /// The package name
+    pub package: String,
+
+    /// The `Cargo.toml` the package is declared in
+    pub manifest_path: PathBuf,
+
+    /// The declared version, as a `Channel::Version`
+    pub rust_version: Channel,
+}
+
+impl DeclaredRustVersion {
+    fn sort_key(&self) -> (u16, u16, u16) {
+        match self.rust_version {
+            Channel::Version(major, minor, patch) => (major, minor, patch.unwrap_or_default()),
+            _ => (0, 0, 0),
+        }
+    }
+}
+
+/// A workspace member whose `Cargo.toml` could not be read or declared an
+/// invalid `rust-version`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidMember {
+    /// The `Cargo.toml` of the member
+    pub manifest_path: PathBuf,
+
+    /// Why the member's `rust-version` could not be determined
+    pub reason: String,
+}
+
+impl InvalidMember {
+    fn new(manifest_path: PathBuf, error: &Error) -> InvalidMember {
+        InvalidMember {
+            manifest_path,
+            reason: error.to_string(),
+        }
+    }
+}
+
+/// The `rust-version`s declared by the packages of a workspace
+#[derive(Clone, Debug, Default)]
+pub struct WorkspaceRustVersions {
+    /// The packages which declare a `rust-version`
+    pub declared: Vec<DeclaredRustVersion>,
+
+    /// The members whose `rust-version` could not be determined
+    pub invalid: Vec<InvalidMember>,
+}
+
+#[derive(Deserialize)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+    workspace: Option<CargoWorkspace>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CargoPackage {
+    name: String,
+    rust_version: Option<Inheritable>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Inheritable {
+    Value(String),
+    Workspace { workspace: bool },
+}
+
+#[derive(Deserialize)]
+struct CargoWorkspace {
+    #[serde(default)]
+    members: Vec<String>,
+
+    #[serde(default)]
+    exclude: Vec<String>,
+
+    package: Option<CargoWorkspacePackage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CargoWorkspacePackage {
+    rust_version: Option<String>,
+}
+
+fn read_cargo_manifest(path: &Path) -> Result<CargoManifest, Error> {
+    let text = std::fs::read_to_string(path)?;
+    basic_toml::from_str(&text).map_err(|e| Error::CargoManifest(path.display().to_string(), e.to_string()))
+}
+
+/// Parses a `rust-version` of the form `major[.minor[.patch]]`. A missing
+/// minor version is taken to be `0`.
+fn parse_rust_version(manifest_path: &Path, version: &str) -> Result<Channel, Error> {
+    let components: Result<Vec<u16>, _> = version.split('.').map(u16::from_str).collect();
+    match components.as_deref() {
+        Ok([major]) => Ok(Channel::Version(*major, 0, None)),
+        Ok([major, minor]) => Ok(Channel::Version(*major, *minor, None)),
+        Ok([major, minor, patch]) => Ok(Channel::Version(*major, *minor, Some(*patch))),
+        _ => Err(Error::CargoManifest(
+            manifest_path.display().to_string(),
+            format!("invalid rust-version {:?}", version),
+        )),
+    }
+}
+
+/// Matches a file name against a pattern in which `*` matches any sequence of
+/// characters and `?` any single character
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Expands a workspace member pattern (e.g. `crates/*`) to the directories it
+/// matches
+fn expand_member(root: &Path, pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut directories = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut expanded = Vec::new();
+        for directory in directories {
+            if component.contains(['*', '?']) {
+                let Ok(entries) = std::fs::read_dir(&directory) else {
+                    continue;
+                };
+                for entry in entries {
+                    let entry = entry?;
+                    let matches = entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|n| matches_wildcard(component, n));
+                    if matches && entry.file_type()?.is_dir() {
+                        expanded.push(entry.path());
+                    }
+                }
+            } else {
+                expanded.push(directory.join(component));
+            }
+        }
+        directories = expanded;
+    }
+    directories.sort();
+    Ok(directories)
+}
+
+/// Reads the `rust-version` of the root package and every workspace member of
+/// the workspace rooted at `root`. Members which inherit `rust-version` from
+/// the workspace use the value in `[workspace.package]`. Packages which do not
+/// declare a `rust-version` are omitted. Only a failure to read the root
+/// `Cargo.toml` is an error; members which cannot be read or declare an
+/// invalid `rust-version` are reported individually.
+pub fn workspace_rust_versions(root: &Path) -> Result<WorkspaceRustVersions, Error> {
+    let root_path = root.join("Cargo.toml");
+    let root_manifest = read_cargo_manifest(&root_path)?;
+    let workspace_version = root_manifest
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.package.as_ref())
+        .and_then(|package| package.rust_version.as_deref());
+
+    let mut manifests = vec![root_path.clone()];
+    if let Some(workspace) = &root_manifest.workspace {
+        let mut excluded = Vec::new();
+        for pattern in &workspace.exclude {
+            excluded.extend(expand_member(root, pattern)?);
+        }
+        for pattern in &workspace.members {
+            for directory in expand_member(root, pattern)? {
+                let path = directory.join("Cargo.toml");
+                if !excluded.contains(&directory) && path.is_file() && !manifests.contains(&path) {
+                    manifests.push(path);
+                }
+            }
+        }
+    }
+
+    let mut result = WorkspaceRustVersions::default();
+    for path in manifests {
+        let manifest = if path == root_path {
+            None
+        } else {
+            match read_cargo_manifest(&path) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    result.invalid.push(InvalidMember::new(path, &e));
+                    continue;
+                }
+            }
+        };
+        let Some(package) = manifest.as_ref().unwrap_or(&root_manifest).package.as_ref() else {
+            continue;
+        };
+        let version = match &package.rust_version {
+            Some(Inheritable::Value(version)) => Ok(version.as_str()),
+            Some(Inheritable::Workspace { workspace: true }) => workspace_version.ok_or_else(|| {
+                Error::CargoManifest(
+                    path.display().to_string(),
+                    "rust-version inherited from workspace, which does not declare one".to_string(),
+                )
+            }),
+            Some(Inheritable::Workspace { workspace: false }) | None => continue,
+        };
+        match version.and_then(|version| parse_rust_version(&path, version)) {
+            Ok(rust_version) => result.declared.push(DeclaredRustVersion {
+                package: package.name.clone(),
+                rust_version,
+                manifest_path: path,
+            }),
+            Err(e) => result.invalid.push(InvalidMember::new(path, &e)),
+        }
+    }
+    Ok(result)
+}
+
+/// An install plan for testing a workspace against its minimum supported Rust
+/// version
+#[derive(Clone, Debug)]
+pub struct MsrvPlan {
+    /// The toolchain for the lowest `rust-version` in the workspace
+    pub toolchain: Toolchain,
+
+    /// The packages which declare the lowest `rust-version`
+    pub packages: Vec<DeclaredRustVersion>,
+
+    /// The members whose `rust-version` could not be determined, and so were
+    /// not considered
+    pub invalid: Vec<InvalidMember>,
+
+    /// The install plan against the toolchain's manifest. Requested
+    /// components which did not exist or were unavailable in that manifest
+    /// are listed in `InstallPlan::missing` rather than causing an error.
+    pub plan: InstallPlan,
+}
+
+impl MsrvPlan {
+    /// Plans the installation of `spec` on `host` for the lowest
+    /// `rust-version` declared in the workspace rooted at `root`. Returns
+    /// `None` if no package declares a `rust-version`.
+    pub fn for_workspace<F: Fetcher>(
+        fetcher: &ManifestFetcher<F>,
+        root: &Path,
+        host: &Triple,
+        spec: &InstallSpec,
+    ) -> Result<Option<MsrvPlan>, Error> {
+        let WorkspaceRustVersions { declared, invalid } = workspace_rust_versions(root)?;
+        let Some(minimum) = declared.iter().map(DeclaredRustVersion::sort_key).min() else {
+            return Ok(None);
+        };
+        let packages: Vec<DeclaredRustVersion> = declared
+            .into_iter()
+            .filter(|declared| declared.sort_key() == minimum)
+            .collect();
+        let toolchain = Toolchain {
+            channel: packages[0].rust_version.clone(),
+            date: None,
+            host: Some(host.clone()),
+        };
+        let manifest = fetcher
+            .fetch_manifest(&toolchain)?
+            .ok_or_else(|| Error::NotFound(toolchain.manifest_url_for_server(fetcher.dist_server())))?;
+        let plan = manifest.plan_install_available(host, spec)?;
+        Ok(Some(MsrvPlan {
+            toolchain,
+            packages,
+            invalid,
+            plan,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rust_versions() {
+        let path = Path::new("Cargo.toml");
+        let parse = |version| parse_rust_version(path, version).ok();
+        assert_eq!(parse("1"), Some(Channel::Version(1, 0, None)));
+        assert_eq!(parse("1.70"), Some(Channel::Version(1, 70, None)));
+        assert_eq!(parse("1.70.1"), Some(Channel::Version(1, 70, Some(1))));
+        for invalid in ["", "1.", "1.70.1.2", "stable", "1.72-beta", "^1.70"] {
+            assert_eq!(parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_members_individually() {
+        let root = std::env::temp_dir().join(format!("rtm-msrv-test-{}", std::process::id()));
+        let members = [
+            ("a", "[package]\nname = \"a\"\nrust-version = \"1.70\"\n"),
+            ("b", "[package]\nname = \"b\"\nrust-version.workspace = true\n"),
+            ("c", "[package]\nname = \"c\"\nrust-version = \"latest\"\n"),
+            ("d", "[package\n"),
+        ];
+        for (name, text) in members {
+            std::fs::create_dir_all(root.join("crates").join(name)).unwrap();
+            std::fs::write(root.join("crates").join(name).join("Cargo.toml"), text).unwrap();
+        }
+        let workspace = "[workspace]\nmembers = [\"crates/*\"]\n[workspace.package]\nrust-version = \"1\"\n";
+        std::fs::write(root.join("Cargo.toml"), workspace).unwrap();
+
+        let versions = workspace_rust_versions(&root).unwrap();
+        let declared: Vec<(&str, String)> = versions
+            .declared
+            .iter()
+            .map(|d| (d.package.as_str(), d.rust_version.to_string()))
+            .collect();
+        assert_eq!(declared, [("a", "1.70".to_string()), ("b", "1.0".to_string())]);
+        let invalid: Vec<&Path> = versions.invalid.iter().map(|i| i.manifest_path.as_path()).collect();
+        assert_eq!(
+            invalid,
+            [
+                root.join("crates/c/Cargo.toml").as_path(),
+                root.join("crates/d/Cargo.toml").as_path()
+            ]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
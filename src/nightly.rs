@@ -0,0 +1,642 @@
+use crate::fetch::{Fetcher, ManifestFetcher};
+use crate::hash_value::AbbreviatedHash;
+use crate::manifest::{Compression, Package};
+use crate::mirror::{write_with_digest, Mirror};
+use crate::plan::{InstallPlan, MissingComponent};
+use crate::toolchain::Channel;
+use crate::{Error, HashValue, InstallSpec, Manifest, Toolchain};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use target_lexicon::Triple;
+
+/// How many days of nightlies are searched unless configured otherwise
+pub const DEFAULT_HORIZON_DAYS: u32 = 30;
+
+/// Why a nightly was not suitable
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Rejection {
+    /// No nightly manifest was published for the date
+    NoManifest,
+
+    /// The manifest for the date could not be parsed
+    Invalid(String),
+
+    /// The install specification could not be resolved against the manifest
+    /// (e.g. the host or profile was unknown)
+    Unresolvable(String),
+
+    /// Some requested components were unknown or unavailable
+    Missing(Vec<MissingComponent>),
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Rejection::NoManifest => write!(formatter, "no manifest"),
+            Rejection::Invalid(message) => write!(formatter, "invalid manifest: {}", message),
+            Rejection::Unresolvable(message) => write!(formatter, "{}", message),
+            Rejection::Missing(missing) => {
+                let missing: Vec<String> = missing.iter().map(ToString::to_string).collect();
+                write!(formatter, "missing {}", missing.join(", "))
+            }
+        }
+    }
+}
+
+/// A nightly which was considered and found unsuitable
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RejectedNightly {
+    /// The nightly date
+    pub date: NaiveDate,
+
+    /// Why the nightly was unsuitable
+    pub reason: Rejection,
+}
+
+/// The outcome of searching for a nightly which satisfies an install
+/// specification
+#[derive(Clone, Debug)]
+pub struct NightlySearchResult {
+    /// The most recent suitable nightly, if one was found within the horizon
+    pub toolchain: Option<Toolchain>,
+
+    /// The more recent nightlies which were rejected, newest first
+    pub rejected: Vec<RejectedNightly>,
+}
+
+/// Searches back through nightly toolchains for the most recent one on which
+/// every requested component is available for a host, as Rustup does when
+/// updating a nightly toolchain with components which are sometimes missing.
+#[derive(Clone, Debug)]
+pub struct NightlySearch {
+    host: Triple,
+    spec: InstallSpec,
+    start: Option<NaiveDate>,
+    horizon_days: u32,
+}
+
+impl NightlySearch {
+    /// Constructs a search for nightlies on which `spec` can be installed on
+    /// `host`
+    #[must_use]
+    pub fn new(host: Triple, spec: InstallSpec) -> NightlySearch {
+        NightlySearch {
+            host,
+            spec,
+            start: None,
+            horizon_days: DEFAULT_HORIZON_DAYS,
+        }
+    }
+
+    /// Sets the date of the newest nightly considered. By default this is the
+    /// date of the current nightly.
+    #[must_use]
+    pub fn with_start(mut self, start: NaiveDate) -> NightlySearch {
+        self.start = Some(start);
+        self
+    }
+
+    /// Sets how many days of nightlies are considered, including the start
+    /// date
+    #[must_use]
+    pub fn with_horizon_days(mut self, horizon_days: u32) -> NightlySearch {
+        self.horizon_days = horizon_days;
+        self
+    }
+
+    /// Walks back one day at a time from the start date until a suitable
+    /// nightly is found or the horizon is reached. Failures to fetch a
+    /// manifest are returned as errors; all other problems with a nightly
+    /// are recorded as rejections.
+    pub fn run<F: Fetcher>(&self, fetcher: &ManifestFetcher<F>) -> Result<NightlySearchResult, Error> {
+        let mut result = NightlySearchResult {
+            toolchain: None,
+            rejected: Vec::new(),
+        };
+        let start = if let Some(start) = self.start {
+            start
+        } else {
+            match fetcher.fetch_manifest(&nightly(None))? {
+                Some(manifest) => manifest.get_date(),
+                None => return Ok(result),
+            }
+        };
+        for date in start.iter_days().rev().take(self.horizon_days as usize) {
+            let toolchain = Toolchain {
+                channel: Channel::Nightly,
+                date: Some(date),
+                host: Some(self.host.clone()),
+            };
+            match self.check(fetcher, &toolchain)? {
+                None => {
+                    result.toolchain = Some(toolchain);
+                    break;
+                }
+                Some(reason) => result.rejected.push(RejectedNightly { date, reason }),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns why a nightly is unsuitable, or `None` if it is suitable
+    fn check<F: Fetcher>(
+        &self,
+        fetcher: &ManifestFetcher<F>,
+        toolchain: &Toolchain,
+    ) -> Result<Option<Rejection>, Error> {
+        Ok(evaluate(fetcher, toolchain, &self.host, &self.spec)?.err())
+    }
+}
+
+/// Fetches the manifest for a toolchain and plans the installation of a
+/// specification, returning why the toolchain is unsuitable if any requested
+/// component is unavailable
+fn evaluate<F: Fetcher>(
+    fetcher: &ManifestFetcher<F>,
+    toolchain: &Toolchain,
+    host: &Triple,
+    spec: &InstallSpec,
+) -> Result<Result<(Manifest, InstallPlan), Rejection>, Error> {
+    let Some(text) = fetcher.fetch_manifest_text(toolchain)? else {
+        return Ok(Err(Rejection::NoManifest));
+    };
+    let manifest = match Manifest::try_from(text.as_str()) {
+        Ok(manifest) => manifest,
+        Err(e) => return Ok(Err(Rejection::Invalid(e.to_string()))),
+    };
+    let plan = match manifest
+        .plan_install_available(host, spec)
+        .and_then(|plan| plan.to_downloads(&manifest).map(|_| plan))
+    {
+        Ok(plan) => plan,
+        Err(e) => return Ok(Err(Rejection::Unresolvable(e.to_string()))),
+    };
+    if plan.is_complete() {
+        Ok(Ok((manifest, plan)))
+    } else {
+        Ok(Err(Rejection::Missing(plan.missing().to_vec())))
+    }
+}
+
+/// Where a commit lies relative to the nightlies in a date range
+#[derive(Clone, Debug)]
+pub struct CommitBracket {
+    /// The last nightly built from a commit which precedes the commit, or
+    /// `None` if every nightly in the range contains it
+    pub before: Option<Toolchain>,
+
+    /// The first nightly built from the commit or one which follows it, or
+    /// `None` if no nightly in the range contains it
+    pub first_containing: Option<Toolchain>,
+
+    /// Whether `first_containing` was built from exactly the commit
+    pub exact: bool,
+}
+
+fn nightly(date: Option<NaiveDate>) -> Toolchain {
+    Toolchain {
+        channel: Channel::Nightly,
+        date,
+        host: None,
+    }
+}
+
+/// Fetches the manifest for a nightly, treating a manifest which cannot be
+/// parsed as missing
+fn fetch_nightly<F: Fetcher>(fetcher: &ManifestFetcher<F>, date: NaiveDate) -> Result<Option<Manifest>, Error> {
+    match fetcher.fetch_manifest_text(&nightly(Some(date)))? {
+        Some(text) => Ok(Manifest::try_from(text.as_str()).ok()),
+        None => Ok(None),
+    }
+}
+
+/// Returns the earliest nightly between `start` and `end` (inclusive) built
+/// from a rust-lang/rust commit, checking each date in turn. The commit may be
+/// abbreviated.
+pub fn find_nightly_with_commit<F: Fetcher>(
+    fetcher: &ManifestFetcher<F>,
+    commit: &AbbreviatedHash,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Option<Toolchain>, Error> {
+    for date in start.iter_days().take_while(|date| *date <= end) {
+        let found = fetch_nightly(fetcher, date)?
+            .and_then(|manifest| manifest.get_rustc_commit().cloned())
+            .is_some_and(|candidate| commit.is_prefix_of(&candidate));
+        if found {
+            return Ok(Some(nightly(Some(date))));
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the nightlies between `start` and `end` (inclusive) either side of a
+/// rust-lang/rust commit which may not have been built as a nightly itself.
+///
+/// `commits` is the history of the repository, oldest first (e.g. the reversed
+/// output of `git rev-list`), and must contain the commit, which may be
+/// abbreviated. The nightlies are
+/// binary searched, so only a few manifests are fetched. Nightlies which are
+/// missing or were built from a commit not in `commits` are skipped.
+pub fn bracket_commit<F: Fetcher>(
+    fetcher: &ManifestFetcher<F>,
+    commit: &AbbreviatedHash,
+    commits: &[HashValue],
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<CommitBracket, Error> {
+    let position = |commit: &HashValue| commits.iter().position(|c| c == commit);
+    let target = commits
+        .iter()
+        .position(|c| commit.is_prefix_of(c))
+        .ok_or_else(|| Error::CommitNotInHistory(commit.to_string()))?;
+    let mut built_from = HashMap::new();
+    let (before, after) = bisect_dates::<_, Error>(start, end, |date| {
+        let Some(rustc_commit) = fetch_nightly(fetcher, date)?.and_then(|m| m.get_rustc_commit().cloned()) else {
+            return Ok(None);
+        };
+        let Some(nightly_position) = position(&rustc_commit) else {
+            return Ok(None);
+        };
+        built_from.insert(date, nightly_position);
+        Ok(Some(nightly_position >= target))
+    })?;
+    Ok(CommitBracket {
+        before: before.map(|date| nightly(Some(date))),
+        first_containing: after.map(|date| nightly(Some(date))),
+        exact: after.is_some_and(|date| built_from.get(&date) == Some(&target)),
+    })
+}
+
+/// A nightly toolchain whose packages have been downloaded during a bisection
+#[derive(Clone, Debug)]
+pub struct BisectCandidate {
+    /// The toolchain
+    pub toolchain: Toolchain,
+
+    /// The packages of the toolchain, along with the location each tarball
+    /// was downloaded to
+    pub packages: Vec<(Package, PathBuf)>,
+}
+
+/// The outcome of a bisection
+#[derive(Clone, Debug)]
+pub struct BisectResult {
+    /// The newest nightly for which the predicate returned `false`
+    pub last_good: Option<Toolchain>,
+
+    /// The oldest nightly for which the predicate returned `true`
+    pub first_bad: Option<Toolchain>,
+
+    /// The nightlies which were skipped, in the order they were considered
+    pub skipped: Vec<RejectedNightly>,
+}
+
+/// Binary searches nightly toolchains for the first one exhibiting a
+/// regression, as `cargo-bisect-rustc` does for nightlies.
+///
+/// For each nightly considered, the packages needed to install the
+/// specification on the host are downloaded into a directory laid out like a
+/// mirror (so tarballs already present are not downloaded again) and passed
+/// to the predicate. The bisection does not install anything itself:
+/// unpacking the tarballs and testing the toolchain is left to the predicate.
+/// Nightlies whose manifests are missing or on which a requested component is
+/// unavailable are skipped.
+#[derive(Clone, Debug)]
+pub struct Bisection {
+    host: Triple,
+    spec: InstallSpec,
+    start: NaiveDate,
+    end: NaiveDate,
+    downloads: Mirror,
+}
+
+impl Bisection {
+    /// Constructs a bisection of the nightlies between `start` and `end`
+    /// (inclusive), downloading packages into the specified directory
+    #[must_use]
+    pub fn new(host: Triple, spec: InstallSpec, start: NaiveDate, end: NaiveDate, downloads: &Path) -> Bisection {
+        Bisection {
+            host,
+            spec,
+            start,
+            end,
+            downloads: Mirror::new(downloads),
+        }
+    }
+
+    /// Runs the bisection. The predicate returns `true` if a toolchain
+    /// exhibits the regression; it is assumed that nightlies before the
+    /// regression do not and nightlies after it do. An error returned by the
+    /// predicate (e.g. because the toolchain could not be installed) ends the
+    /// bisection.
+    pub fn run<F, P, E>(&self, fetcher: &ManifestFetcher<F>, mut predicate: P) -> Result<BisectResult, E>
+    where
+        F: Fetcher,
+        P: FnMut(&BisectCandidate) -> Result<bool, E>,
+        E: From<Error>,
+    {
+        let mut skipped = Vec::new();
+        let (last_good, first_bad) = bisect_dates(self.start, self.end, |date| -> Result<_, E> {
+            let toolchain = Toolchain {
+                host: Some(self.host.clone()),
+                ..nightly(Some(date))
+            };
+            let (manifest, plan) = match evaluate(fetcher, &toolchain, &self.host, &self.spec)? {
+                Ok(evaluation) => evaluation,
+                Err(reason) => {
+                    skipped.push(RejectedNightly { date, reason });
+                    return Ok(None);
+                }
+            };
+            let packages = self.download(fetcher, plan.to_downloads(&manifest)?)?;
+            Ok(Some(predicate(&BisectCandidate { toolchain, packages })?))
+        })?;
+        let toolchain = |date| Toolchain {
+            host: Some(self.host.clone()),
+            ..nightly(Some(date))
+        };
+        Ok(BisectResult {
+            last_good: last_good.map(toolchain),
+            first_bad: first_bad.map(toolchain),
+            skipped,
+        })
+    }
+
+    /// Downloads one tarball for each package, preferring the best
+    /// compression. A package without any tarballs cannot be installed, so
+    /// is reported as unavailable.
+    fn download<F: Fetcher>(
+        &self,
+        fetcher: &ManifestFetcher<F>,
+        packages: Vec<Package>,
+    ) -> Result<Vec<(Package, PathBuf)>, Error> {
+        let preference = |compression: &Compression| match compression {
+            Compression::Xz => 0,
+            Compression::Gzip => 1,
+            Compression::None => 2,
+        };
+        let mut result = Vec::with_capacity(packages.len());
+        for package in packages {
+            let Some((_, binary)) = package.tarballs.iter().min_by_key(|(c, _)| preference(c)) else {
+                return Err(Error::PackageUnavailable(package.name, package.supported_target));
+            };
+            let path = self.downloads.path_for_url(&binary.url)?;
+            if binary.verify_file(&path)? != Some(true) {
+                write_with_digest(&path, &fetcher.fetch_binary(binary)?)?;
+            }
+            result.push((package, path));
+        }
+        Ok(result)
+    }
+}
+
+/// Binary searches the dates between `start` and `end` (inclusive) for the
+/// point at which `probe` changes from `false` to `true`, returning the last
+/// date found to be `false` and the first found to be `true`. Dates for which
+/// `probe` returns `None` are skipped.
+fn bisect_dates<P, E>(
+    start: NaiveDate,
+    end: NaiveDate,
+    mut probe: P,
+) -> Result<(Option<NaiveDate>, Option<NaiveDate>), E>
+where
+    P: FnMut(NaiveDate) -> Result<Option<bool>, E>,
+{
+    let mut candidates: Vec<NaiveDate> = start.iter_days().take_while(|date| *date <= end).collect();
+    let (mut last_false, mut first_true) = (None, None);
+    while !candidates.is_empty() {
+        let middle = candidates.len() / 2;
+        let date = candidates[middle];
+        match probe(date)? {
+            Some(true) => {
+                first_true = Some(date);
+                candidates.truncate(middle);
+            }
+            Some(false) => {
+                last_false = Some(date);
+                candidates.drain(..=middle);
+            }
+            None => {
+                candidates.remove(middle);
+            }
+        }
+    }
+    Ok((last_false, first_true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::{DirectoryFetcher, DEFAULT_DIST_SERVER};
+    use crate::testing::{manifest_text, publish_manifest, temp_dir, TARBALL};
+    use crate::ComponentName;
+    use chrono::Datelike;
+    use std::str::FromStr;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    /// Serves files from memory
+    struct MemoryFetcher(HashMap<String, Vec<u8>>);
+
+    impl Fetcher for MemoryFetcher {
+        fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.0.get(url).cloned())
+        }
+    }
+
+    /// Publishes a nightly for each day of June 2023 from the 1st to the 8th,
+    /// except the 3rd. Clippy is unavailable on the 6th.
+    fn nightlies() -> ManifestFetcher<MemoryFetcher> {
+        let mut files = HashMap::new();
+        for day in (1..=8).filter(|day| *day != 3) {
+            let date = format!("2023-06-{:02}", day);
+            let commit = "871b5952023139738f72eba235063575062bc2e9";
+            let text = manifest_text(&date, "1.72.0-nightly", commit, HOST, &[("clippy", day != 6)]);
+            for name in ["rust", "clippy"] {
+                let url = format!("{}/dist/{}/{}-{}.tar.xz", DEFAULT_DIST_SERVER, date, name, HOST);
+                files.insert(url, TARBALL.to_vec());
+            }
+            let toolchain = nightly(NaiveDate::from_str(&date).ok());
+            files.insert(
+                toolchain.manifest_url_for_server(DEFAULT_DIST_SERVER),
+                text.into_bytes(),
+            );
+        }
+        ManifestFetcher::new(MemoryFetcher(files))
+    }
+
+    fn bisection(downloads: &Path) -> Bisection {
+        let host = Triple::from_str(HOST).unwrap();
+        let spec = InstallSpec::new("default").with_component(ComponentName::new("clippy"));
+        let date = |day| NaiveDate::from_ymd_opt(2023, 6, day).unwrap();
+        Bisection::new(host, spec, date(1), date(8), downloads)
+    }
+
+    #[test]
+    fn bisection_finds_first_bad_nightly() {
+        let downloads = std::env::temp_dir().join(format!("rtm-bisect-test-{}", std::process::id()));
+        let first_bad = NaiveDate::from_ymd_opt(2023, 6, 5).unwrap();
+        let result = bisection(&downloads)
+            .run(&nightlies(), |candidate| -> Result<bool, Error> {
+                let names: Vec<&str> = candidate.packages.iter().map(|(p, _)| p.name.as_str()).collect();
+                assert_eq!(names, ["clippy"]);
+                assert_eq!(std::fs::read(&candidate.packages[0].1)?, TARBALL);
+                Ok(candidate.toolchain.date.unwrap() >= first_bad)
+            })
+            .unwrap();
+        let date = |toolchain: Option<Toolchain>| toolchain.and_then(|t| t.date).map(|d| d.to_string());
+        assert_eq!(date(result.last_good).as_deref(), Some("2023-06-04"));
+        assert_eq!(date(result.first_bad).as_deref(), Some("2023-06-05"));
+        std::fs::remove_dir_all(&downloads).unwrap();
+    }
+
+    #[test]
+    fn bisection_skips_unsuitable_nightlies() {
+        let downloads = std::env::temp_dir().join(format!("rtm-bisect-skip-test-{}", std::process::id()));
+        let first_bad = NaiveDate::from_ymd_opt(2023, 6, 7).unwrap();
+        let result = bisection(&downloads)
+            .run(&nightlies(), |candidate| -> Result<bool, Error> {
+                Ok(candidate.toolchain.date.unwrap() >= first_bad)
+            })
+            .unwrap();
+        let skipped: Vec<String> = result.skipped.iter().map(|r| r.date.to_string()).collect();
+        assert_eq!(skipped, ["2023-06-06"]);
+        assert!(matches!(result.skipped[0].reason, Rejection::Missing(_)));
+        assert_eq!(result.last_good.and_then(|t| t.date).unwrap().to_string(), "2023-06-05");
+        std::fs::remove_dir_all(&downloads).unwrap();
+    }
+
+    #[test]
+    fn bisection_stops_on_predicate_error() {
+        let downloads = std::env::temp_dir().join(format!("rtm-bisect-error-test-{}", std::process::id()));
+        let mut calls = 0;
+        let result = bisection(&downloads).run(&nightlies(), |_| {
+            calls += 1;
+            Err(Error::NotFound("installer".to_string()))
+        });
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        assert_eq!(calls, 1);
+        std::fs::remove_dir_all(&downloads).unwrap();
+    }
+
+    /// Publishes a nightly for each day of June 2023 from the 1st to the 6th,
+    /// except the 3rd. Clippy is unavailable from the 4th.
+    fn published_nightlies(root: &Path) -> ManifestFetcher<DirectoryFetcher> {
+        for day in (1..=6).filter(|day| *day != 3) {
+            let date = format!("2023-06-{:02}", day);
+            let commit = "871b5952023139738f72eba235063575062bc2e9";
+            let text = manifest_text(&date, "1.72.0-nightly", commit, HOST, &[("clippy", day < 4)]);
+            publish_manifest(root, "nightly", &text, day == 6);
+        }
+        ManifestFetcher::new(DirectoryFetcher::new(root, DEFAULT_DIST_SERVER))
+    }
+
+    fn clippy_search() -> NightlySearch {
+        let spec = InstallSpec::new("default").with_component(ComponentName::new("clippy"));
+        NightlySearch::new(Triple::from_str(HOST).unwrap(), spec)
+    }
+
+    fn rejected_days(result: &NightlySearchResult) -> Vec<u32> {
+        result.rejected.iter().map(|r| r.date.day()).collect()
+    }
+
+    #[test]
+    fn search_finds_most_recent_suitable_nightly() {
+        let root = temp_dir("nightly-search");
+        let result = clippy_search().run(&published_nightlies(&root)).unwrap();
+        assert_eq!(
+            result.toolchain.as_ref().and_then(|t| t.date).unwrap().to_string(),
+            "2023-06-02"
+        );
+        assert_eq!(rejected_days(&result), [6, 5, 4, 3]);
+        assert!(matches!(result.rejected[0].reason, Rejection::Missing(_)));
+        assert_eq!(result.rejected[3].reason, Rejection::NoManifest);
+
+        let start = NaiveDate::from_ymd_opt(2023, 6, 2).unwrap();
+        let result = clippy_search()
+            .with_start(start)
+            .run(&published_nightlies(&root))
+            .unwrap();
+        assert_eq!(result.toolchain.and_then(|t| t.date), Some(start));
+        assert!(result.rejected.is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn search_stops_at_horizon() {
+        let root = temp_dir("nightly-horizon");
+        let result = clippy_search()
+            .with_horizon_days(3)
+            .run(&published_nightlies(&root))
+            .unwrap();
+        assert!(result.toolchain.is_none());
+        assert_eq!(rejected_days(&result), [6, 5, 4]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    /// A commit whose hash repeats one hexadecimal digit
+    fn commit(n: u32) -> HashValue {
+        HashValue::from_str(&format!("{:x}", n).repeat(40)).unwrap()
+    }
+
+    /// Publishes a nightly for each day of June 2023 from the 1st to the 5th,
+    /// except the 3rd, built from commits 2, 4, 6 and 8 respectively
+    fn commit_nightlies(root: &Path) -> ManifestFetcher<DirectoryFetcher> {
+        for (day, n) in [(1, 2), (2, 4), (4, 6), (5, 8)] {
+            let date = format!("2023-06-{:02}", day);
+            let text = manifest_text(&date, "1.72.0-nightly", &commit(n).to_string(), HOST, &[]);
+            publish_manifest(root, "nightly", &text, false);
+        }
+        ManifestFetcher::new(DirectoryFetcher::new(root, DEFAULT_DIST_SERVER))
+    }
+
+    fn abbreviated(n: u32) -> AbbreviatedHash {
+        AbbreviatedHash::from_str(&format!("{:X}", n).repeat(9)).unwrap()
+    }
+
+    fn day(toolchain: Option<Toolchain>) -> Option<u32> {
+        toolchain.and_then(|t| t.date).map(|date| date.day())
+    }
+
+    #[test]
+    fn finds_nightly_built_from_commit() {
+        let root = temp_dir("nightly-commit");
+        let fetcher = commit_nightlies(&root);
+        let (start, end) = (
+            NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 5).unwrap(),
+        );
+        let find = |n, start| day(find_nightly_with_commit(&fetcher, &abbreviated(n), start, end).unwrap());
+        assert_eq!(find(6, start), Some(4));
+        assert_eq!(find(8, start), Some(5));
+        assert_eq!(find(2, NaiveDate::from_ymd_opt(2023, 6, 2).unwrap()), None);
+        assert_eq!(find(5, start), None);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn brackets_commits_between_nightlies() {
+        let root = temp_dir("nightly-bracket");
+        let fetcher = commit_nightlies(&root);
+        let commits: Vec<HashValue> = (1..=10).map(commit).collect();
+        let (start, end) = (
+            NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 5).unwrap(),
+        );
+        let bracket = |n| {
+            let bracket = bracket_commit(&fetcher, &abbreviated(n), &commits, start, end).unwrap();
+            (day(bracket.before), day(bracket.first_containing), bracket.exact)
+        };
+        assert_eq!(bracket(6), (Some(2), Some(4), true));
+        assert_eq!(bracket(5), (Some(2), Some(4), false));
+        assert_eq!(bracket(3), (Some(1), Some(2), false));
+        assert_eq!(bracket(1), (None, Some(1), false));
+        assert_eq!(bracket(10), (Some(5), None, false));
+
+        let result = bracket_commit(&fetcher, &abbreviated(11), &commits, start, end);
+        assert!(matches!(result, Err(Error::CommitNotInHistory(commit)) if commit == "bbbbbbbbb"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
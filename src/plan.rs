@@ -0,0 +1,595 @@
+use crate::host::HostFallbacks;
+use crate::manifest::Package;
+use crate::{Error, Manifest, SupportedTarget};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use target_lexicon::Triple;
+
+/// The reason a package was included in an install plan
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Provenance {
+    /// The component was listed in the requested profile
+    Profile {
+        /// The profile name
+        profile: String,
+
+        /// The component name as listed in the profile
+        component: String,
+    },
+
+    /// The component was explicitly requested
+    Component {
+        /// The component name as requested
+        component: String,
+    },
+
+    /// The standard library was requested for a target
+    Target {
+        /// The target triple
+        target: String,
+    },
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Provenance::Profile { profile, component } => {
+                write!(formatter, "profile {} -> component {}", profile, component)
+            }
+            Provenance::Component { component } => write!(formatter, "component {}", component),
+            Provenance::Target { target } => write!(formatter, "target {}", target),
+        }
+    }
+}
+
+/// A package rename applied while resolving a component name
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Rename {
+    /// The old package name, as requested
+    pub from: String,
+
+    /// The package name in the manifest
+    pub to: String,
+}
+
+/// How a requested component was resolved to a package
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Resolution {
+    /// Why the component was requested
+    pub provenance: Provenance,
+
+    /// The rename applied to the requested name, if it referred to the
+    /// package by an old name
+    pub rename: Option<Rename>,
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}", self.provenance)?;
+        if let Some(rename) = &self.rename {
+            write!(formatter, " -> renamed from {} to {}", rename.from, rename.to)?;
+        }
+        Ok(())
+    }
+}
+
+/// A profile component which was not included in an install plan because it
+/// is not known on the host. Profiles list components for every host (e.g.
+/// `rust-mingw`), so these are skipped rather than treated as errors.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct SkippedComponent {
+    /// The profile name
+    pub profile: String,
+
+    /// The component name as listed in the profile
+    pub component: String,
+}
+
+impl std::fmt::Display for SkippedComponent {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            formatter,
+            "profile {} -> component {} (skipped, unknown on host)",
+            self.profile, self.component
+        )
+    }
+}
+
+/// A package whose build for a compatible host was used because the build for
+/// the host being installed on was unavailable
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HostSubstitution {
+    /// The package name
+    pub package: String,
+
+    /// The host triple the package was requested for
+    #[serde(with = "triple")]
+    pub host: Triple,
+
+    /// The triple of the compatible host whose build is used instead
+    #[serde(with = "triple")]
+    pub fallback: Triple,
+}
+
+/// Serializes a triple as a string
+mod triple {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+
+    pub fn serialize<S>(triple: &Triple, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(triple)
+    }
+
+    pub fn deserialize<'a, D>(deserializer: D) -> Result<Triple, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Triple::from_str(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::fmt::Display for HostSubstitution {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            formatter,
+            "package {} for {} (substituted, unavailable for {})",
+            self.package, self.fallback, self.host
+        )
+    }
+}
+
+/// A requested component which could not be included in an install plan
+/// resolved with `Manifest::plan_install_available`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MissingComponent {
+    /// The requested component (or standard library for a requested target)
+    /// is not known on the host
+    Unknown {
+        /// Why the component was requested
+        provenance: Provenance,
+    },
+
+    /// The component resolved to a package which is not available for its
+    /// target
+    Unavailable {
+        /// How the component was resolved
+        resolution: Resolution,
+
+        /// The package name
+        package: String,
+
+        /// The target the package is built for
+        target: SupportedTarget,
+    },
+}
+
+impl std::fmt::Display for MissingComponent {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            MissingComponent::Unknown { provenance } => write!(formatter, "{} (unknown on host)", provenance),
+            MissingComponent::Unavailable {
+                resolution,
+                package,
+                target,
+            } => write!(
+                formatter,
+                "{} -> package {} for {} (unavailable)",
+                resolution, package, target
+            ),
+        }
+    }
+}
+
+/// A package in an install plan
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PlanEntry {
+    /// The package name
+    pub package: String,
+
+    /// The target the package is built for
+    pub target: SupportedTarget,
+
+    /// How the package came to be included, sorted. A package may be
+    /// included for several reasons.
+    pub resolutions: Vec<Resolution>,
+}
+
+impl PlanEntry {
+    /// Describes each chain of resolution steps which led to the package, e.g.
+    /// `profile default -> component clippy -> renamed from clippy to
+    /// clippy-preview -> package clippy-preview for x86_64-unknown-linux-gnu`
+    #[must_use]
+    pub fn explain(&self) -> Vec<String> {
+        self.resolutions
+            .iter()
+            .map(|resolution| format!("{} -> package {} for {}", resolution, self.package, self.target))
+            .collect()
+    }
+}
+
+/// The packages required to install a toolchain, as produced by
+/// `Manifest::plan_install`. Entries are ordered by package name and then
+/// target.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "SerializedPlan")]
+pub struct InstallPlan {
+    entries: Vec<PlanEntry>,
+    skipped: Vec<SkippedComponent>,
+    missing: Vec<MissingComponent>,
+    substituted: Vec<HostSubstitution>,
+}
+
+/// The serialized form of an install plan, whose lists may be in any order
+#[derive(Deserialize)]
+struct SerializedPlan {
+    entries: Vec<PlanEntry>,
+    skipped: Vec<SkippedComponent>,
+    missing: Vec<MissingComponent>,
+    substituted: Vec<HostSubstitution>,
+}
+
+/// Rebuilds a plan through the same operations used to construct one, so that
+/// its entries are sorted and merged
+impl TryFrom<SerializedPlan> for InstallPlan {
+    type Error = String;
+
+    fn try_from(serialized: SerializedPlan) -> Result<InstallPlan, String> {
+        let mut plan = InstallPlan::default();
+        for entry in serialized.entries {
+            if entry.resolutions.is_empty() {
+                return Err(format!(
+                    "Plan entry for package {} for {} has no resolutions",
+                    entry.package, entry.target
+                ));
+            }
+            for resolution in entry.resolutions {
+                plan.insert(entry.package.clone(), entry.target.clone(), resolution);
+            }
+        }
+        for skipped in serialized.skipped {
+            plan.skip(skipped);
+        }
+        for missing in serialized.missing {
+            plan.add_missing(missing);
+        }
+        plan.substituted = serialized.substituted;
+        Ok(plan)
+    }
+}
+
+impl InstallPlan {
+    /// Adds a package to the plan, or records an additional reason for
+    /// including it if it is already present
+    pub(crate) fn insert(&mut self, package: String, target: SupportedTarget, resolution: Resolution) {
+        let key = (package.as_str(), target.to_string());
+        match self
+            .entries
+            .binary_search_by(|entry| (entry.package.as_str(), entry.target.to_string()).cmp(&key))
+        {
+            Ok(idx) => {
+                let entry = &mut self.entries[idx];
+                if let Err(position) = entry.resolutions.binary_search(&resolution) {
+                    entry.resolutions.insert(position, resolution);
+                }
+            }
+            Err(idx) => self.entries.insert(
+                idx,
+                PlanEntry {
+                    package,
+                    target,
+                    resolutions: vec![resolution],
+                },
+            ),
+        }
+    }
+
+    /// Records a profile component which was skipped
+    pub(crate) fn skip(&mut self, skipped: SkippedComponent) {
+        if let Err(position) = self.skipped.binary_search(&skipped) {
+            self.skipped.insert(position, skipped);
+        }
+    }
+
+    /// Records a requested component which could not be included
+    pub(crate) fn add_missing(&mut self, missing: MissingComponent) {
+        let key = missing.to_string();
+        if let Err(position) = self.missing.binary_search_by(|existing| existing.to_string().cmp(&key)) {
+            self.missing.insert(position, missing);
+        }
+    }
+
+    /// Removes the packages which are not available for their target,
+    /// recording the components which required them as missing
+    pub(crate) fn remove_unavailable(&mut self, manifest: &Manifest) -> Result<(), Error> {
+        let mut available = Vec::with_capacity(self.entries.len());
+        for entry in std::mem::take(&mut self.entries) {
+            match manifest.get_package(&entry.package, &entry.target) {
+                Ok(_) => available.push(entry),
+                Err(Error::PackageUnavailable(..) | Error::PackageUnknown(..)) => {
+                    for resolution in entry.resolutions {
+                        self.add_missing(MissingComponent::Unavailable {
+                            resolution,
+                            package: entry.package.clone(),
+                            target: entry.target.clone(),
+                        });
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.entries = available;
+        Ok(())
+    }
+
+    /// Replaces packages built for `host` which are unavailable with builds
+    /// for the first fallback host on which they are available. The standard
+    /// library is never substituted, since it must match the target being
+    /// compiled for.
+    pub(crate) fn substitute_hosts(
+        &mut self,
+        manifest: &Manifest,
+        host: &Triple,
+        fallbacks: &HostFallbacks,
+    ) -> Result<(), Error> {
+        let native = SupportedTarget::Dependent(host.clone());
+        for entry in std::mem::take(&mut self.entries) {
+            let unavailable = entry.target == native
+                && entry.package != "rust-std"
+                && match manifest.get_package(&entry.package, &entry.target) {
+                    Ok(_) => false,
+                    Err(Error::PackageUnavailable(..) | Error::PackageUnknown(..)) => true,
+                    Err(e) => return Err(e),
+                };
+            let fallback = unavailable
+                .then(|| {
+                    fallbacks.fallbacks_for(host).into_iter().find(|fallback| {
+                        manifest
+                            .get_package(&entry.package, &SupportedTarget::Dependent((*fallback).clone()))
+                            .is_ok()
+                    })
+                })
+                .flatten();
+            let target = if let Some(fallback) = fallback {
+                self.substituted.push(HostSubstitution {
+                    package: entry.package.clone(),
+                    host: host.clone(),
+                    fallback: fallback.clone(),
+                });
+                SupportedTarget::Dependent(fallback.clone())
+            } else {
+                entry.target
+            };
+            for resolution in entry.resolutions {
+                self.insert(entry.package.clone(), target.clone(), resolution);
+            }
+        }
+        Ok(())
+    }
+
+    /// The packages in the plan
+    #[must_use]
+    pub fn entries(&self) -> &[PlanEntry] {
+        &self.entries
+    }
+
+    /// The profile components which were skipped because they are not known
+    /// on the host, sorted
+    #[must_use]
+    pub fn skipped(&self) -> &[SkippedComponent] {
+        &self.skipped
+    }
+
+    /// The requested components which could not be included, sorted by
+    /// description. This is only ever non-empty for plans resolved with
+    /// `Manifest::plan_install_available`.
+    #[must_use]
+    pub fn missing(&self) -> &[MissingComponent] {
+        &self.missing
+    }
+
+    /// The packages for which a compatible host's build was substituted, in
+    /// the order of the plan entries they replaced. This is only ever
+    /// non-empty for plans resolved with
+    /// `Manifest::plan_install_with_fallbacks`.
+    #[must_use]
+    pub fn substituted(&self) -> &[HostSubstitution] {
+        &self.substituted
+    }
+
+    /// Returns `true` if every requested component was included
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Describes how every package in the plan was resolved, one line per
+    /// chain of resolution steps, followed by the substituted packages and
+    /// skipped profile components
+    #[must_use]
+    pub fn explain(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .flat_map(PlanEntry::explain)
+            .chain(self.substituted.iter().map(ToString::to_string))
+            .chain(self.skipped.iter().map(ToString::to_string))
+            .collect()
+    }
+
+    /// Iterates over the packages in the plan
+    pub fn iter(&self) -> std::slice::Iter<'_, PlanEntry> {
+        self.entries.iter()
+    }
+
+    /// The number of packages in the plan
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the plan contains no packages
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entries for a package name (one per target)
+    pub fn get<'a>(&'a self, package: &'a str) -> impl Iterator<Item = &'a PlanEntry> + 'a {
+        self.entries.iter().filter(move |entry| entry.package == package)
+    }
+
+    /// Returns the entry for a package built for a specific target
+    #[must_use]
+    pub fn find(&self, package: &str, target: &SupportedTarget) -> Option<&PlanEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.package == package && &entry.target == target)
+    }
+
+    /// Returns `true` if the plan contains the package for any target
+    #[must_use]
+    pub fn contains(&self, package: &str) -> bool {
+        self.entries.iter().any(|entry| entry.package == package)
+    }
+
+    /// Returns the package names and targets in the plan
+    #[must_use]
+    pub fn packages(&self) -> HashSet<(String, SupportedTarget)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.package.clone(), entry.target.clone()))
+            .collect()
+    }
+
+    /// Returns the package descriptions, including the archives which need to
+    /// be downloaded, in the same order as the plan entries
+    pub fn to_downloads(&self, manifest: &Manifest) -> Result<Vec<Package>, Error> {
+        self.entries
+            .iter()
+            .map(|entry| manifest.get_package(&entry.package, &entry.target))
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a InstallPlan {
+    type IntoIter = std::slice::Iter<'a, PlanEntry>;
+    type Item = &'a PlanEntry;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn component(name: &str) -> Resolution {
+        Resolution {
+            provenance: Provenance::Component {
+                component: name.to_string(),
+            },
+            rename: None,
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let host = SupportedTarget::Dependent(Triple::from_str("x86_64-unknown-linux-gnu").unwrap());
+        let mut plan = InstallPlan::default();
+        plan.insert("rustc".to_string(), host.clone(), component("rustc"));
+        plan.insert(
+            "clippy-preview".to_string(),
+            host.clone(),
+            Resolution {
+                provenance: Provenance::Profile {
+                    profile: "default".to_string(),
+                    component: "clippy".to_string(),
+                },
+                rename: Some(Rename {
+                    from: "clippy".to_string(),
+                    to: "clippy-preview".to_string(),
+                }),
+            },
+        );
+        plan.insert(
+            "rust-src".to_string(),
+            SupportedTarget::Independent,
+            component("rust-src"),
+        );
+        plan.skip(SkippedComponent {
+            profile: "default".to_string(),
+            component: "rust-mingw".to_string(),
+        });
+        plan.add_missing(MissingComponent::Unavailable {
+            resolution: component("miri"),
+            package: "miri-preview".to_string(),
+            target: host,
+        });
+        plan.substituted.push(HostSubstitution {
+            package: "rust-docs".to_string(),
+            host: Triple::from_str("aarch64-apple-darwin").unwrap(),
+            fallback: Triple::from_str("x86_64-apple-darwin").unwrap(),
+        });
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["substituted"][0]["fallback"], "x86_64-apple-darwin");
+        assert_eq!(serde_json::from_value::<InstallPlan>(json).unwrap(), plan);
+    }
+
+    #[test]
+    fn deserialize_restores_order() {
+        let host = SupportedTarget::Dependent(Triple::from_str("x86_64-unknown-linux-gnu").unwrap());
+        let mut plan = InstallPlan::default();
+        for package in ["cargo", "rustc", "rust-std"] {
+            plan.insert(package.to_string(), host.clone(), component(package));
+        }
+        plan.insert("cargo".to_string(), host, component("tools"));
+        let mut json = serde_json::to_value(&plan).unwrap();
+        let entries = json["entries"].as_array_mut().unwrap();
+        entries.reverse();
+        entries[2]["resolutions"].as_array_mut().unwrap().reverse();
+        entries.push(entries[0].clone());
+        let deserialized: InstallPlan = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, plan);
+    }
+
+    #[test]
+    fn missing_components_are_sorted_and_deduplicated() {
+        let mut plan = InstallPlan::default();
+        for name in ["rustfmt", "clippy", "miri", "clippy", "rls"] {
+            plan.add_missing(MissingComponent::Unknown {
+                provenance: component(name).provenance,
+            });
+        }
+        let missing: Vec<String> = plan.missing().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            missing,
+            [
+                "component clippy (unknown on host)",
+                "component miri (unknown on host)",
+                "component rls (unknown on host)",
+                "component rustfmt (unknown on host)",
+            ]
+        );
+    }
+
+    #[test]
+    fn deserialize_rejects_entries_without_resolutions() {
+        let json = serde_json::json!({
+            "entries": [{"package": "rustc", "target": "x86_64-unknown-linux-gnu", "resolutions": []}],
+            "skipped": [],
+            "missing": [],
+            "substituted": [],
+        });
+        let error = serde_json::from_value::<InstallPlan>(json).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Plan entry for package rustc for x86_64-unknown-linux-gnu has no resolutions"
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use crate::fetch::{dist_path, local_path, Fetcher, ManifestFetcher, DEFAULT_DIST_SERVER};
+use crate::manifest::RemoteBinary;
+use crate::mirror::{matches_digest_file, write_with_digest};
+use crate::server::DistSource;
+use crate::{Error, Manifest};
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+/// How long an undated channel manifest is served from the cache before being
+/// revalidated against the upstream server, unless configured otherwise
+pub const DEFAULT_MANIFEST_TTL: Duration = Duration::from_secs(600);
+
+/// Channels whose dated manifests are consulted when a binary is requested
+/// that is not listed in any manifest seen so far
+const LOOKUP_CHANNELS: [&str; 3] = ["nightly", "beta", "stable"];
+
+/// A pull-through cache for a distribution server.
+///
+/// Files are served from a local cache directory laid out like a mirror, or
+/// fetched from the upstream server and stored. Dated manifests and binaries
+/// never change upstream so are cached permanently. Undated channel manifests
+/// are revalidated once they are older than the manifest TTL, falling back to
+/// the cached copy if the upstream server cannot be reached. Binaries are only
+/// stored once verified against the digests in a manifest.
+///
+/// Clients such as rustup fetch a manifest's `.sha256` file and then the
+/// manifest itself, so revalidation is driven by requests for the digest. A
+/// request for the manifest only revalidates once the cached copy is older
+/// than twice the TTL, so that it matches a digest served moments earlier.
+#[derive(Debug)]
+pub struct CachingProxy<F> {
+    cache: PathBuf,
+    upstream: ManifestFetcher<F>,
+    manifest_ttl: Duration,
+    index: Mutex<BinaryIndex>,
+}
+
+#[derive(Debug, Default)]
+struct BinaryIndex {
+    binaries: HashMap<String, RemoteBinary>,
+    manifests: HashSet<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Artefact<'a> {
+    ChannelManifest,
+    DatedManifest,
+    Binary { date: &'a str },
+    Other,
+}
+
+impl<'a> Artefact<'a> {
+    fn classify(path: &'a str) -> Artefact<'a> {
+        let is_manifest = |name: &str| {
+            Path::new(name)
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+                && name.starts_with("channel-rust-")
+        };
+        let components: Vec<&str> = path.split('/').collect();
+        match components.as_slice() {
+            ["dist", name] if is_manifest(name) => Artefact::ChannelManifest,
+            ["dist", date, name] if NaiveDate::from_str(date).is_ok() => {
+                if is_manifest(name) {
+                    Artefact::DatedManifest
+                } else {
+                    Artefact::Binary { date }
+                }
+            }
+            _ => Artefact::Other,
+        }
+    }
+}
+
+impl<F: Fetcher> CachingProxy<F> {
+    /// Constructs a proxy which caches files from `upstream` in the specified
+    /// directory
+    pub fn new<P: Into<PathBuf>>(cache: P, upstream: ManifestFetcher<F>) -> CachingProxy<F> {
+        CachingProxy {
+            cache: cache.into(),
+            upstream,
+            manifest_ttl: DEFAULT_MANIFEST_TTL,
+            index: Mutex::new(BinaryIndex::default()),
+        }
+    }
+
+    /// Sets how long undated channel manifests are served before being
+    /// revalidated
+    #[must_use]
+    pub fn with_manifest_ttl(mut self, manifest_ttl: Duration) -> CachingProxy<F> {
+        self.manifest_ttl = manifest_ttl;
+        self
+    }
+
+    /// Returns the cached file for a path relative to the root of the
+    /// distribution server, fetching it from upstream if required. Returns
+    /// `None` if the file does not exist.
+    pub fn get(&self, path: &str) -> Result<Option<PathBuf>, Error> {
+        let Some(local) = local_path(&self.cache, path) else {
+            return Ok(None);
+        };
+        let artefact_path = path.strip_suffix(".sha256").unwrap_or(path);
+        let Some(artefact_local) = local_path(&self.cache, artefact_path) else {
+            return Ok(None);
+        };
+        match Artefact::classify(artefact_path) {
+            Artefact::ChannelManifest => {
+                let ttl = if local == artefact_local {
+                    self.manifest_ttl.saturating_mul(2)
+                } else {
+                    self.manifest_ttl
+                };
+                if !is_fresh(&artefact_local, ttl)? {
+                    match self.fetch_manifest(artefact_path) {
+                        Ok(Some(text)) => self.store_manifest(&artefact_local, &text)?,
+                        Ok(None) => return Ok(None),
+                        // Serve the stale copy if we have one
+                        Err(e) if !artefact_local.is_file() => return Err(e),
+                        Err(_) => {}
+                    }
+                }
+            }
+            Artefact::DatedManifest => {
+                if !artefact_local.is_file() {
+                    match self.fetch_manifest(artefact_path)? {
+                        Some(text) => self.store_manifest(&artefact_local, &text)?,
+                        None => return Ok(None),
+                    }
+                }
+            }
+            Artefact::Binary { date } => {
+                if !artefact_local.is_file() {
+                    let Some(binary) = self.find_binary(artefact_path, date)? else {
+                        return Ok(None);
+                    };
+                    let data = self.upstream.fetch_binary(&binary)?;
+                    write_with_digest(&artefact_local, &data)?;
+                }
+            }
+            Artefact::Other => {}
+        }
+        Ok(Some(local).filter(|local| local.is_file()))
+    }
+
+    /// Fetches a manifest from upstream, checking it against the upstream
+    /// `.sha256` file if one is published
+    fn fetch_manifest(&self, path: &str) -> Result<Option<String>, Error> {
+        let url = self.upstream.url_for_path(path);
+        let Some(data) = self.upstream.fetcher().fetch(&url)? else {
+            return Ok(None);
+        };
+        let digest_url = format!("{}.sha256", url);
+        if let Some(digest) = self.upstream.fetcher().fetch(&digest_url)? {
+            if !matches_digest_file(&String::from_utf8_lossy(&digest), &data) {
+                return Err(Error::DigestMismatch(url));
+            }
+        }
+        String::from_utf8(data)
+            .map(Some)
+            .map_err(|_| Error::ManifestNotUtf8(url))
+    }
+
+    /// Stores a manifest in the cache and indexes the binaries it lists. Undated
+    /// manifests are also stored under their dated path.
+    fn store_manifest(&self, path: &Path, text: &str) -> Result<(), Error> {
+        let manifest = Manifest::try_from(text)?;
+        write_with_digest(path, text.as_bytes())?;
+        if let Some(file_name) = path.file_name() {
+            let dated = self
+                .cache
+                .join("dist")
+                .join(manifest.get_date().to_string())
+                .join(file_name);
+            if !dated.is_file() {
+                write_with_digest(&dated, text.as_bytes())?;
+            }
+        }
+        self.index_manifest(path, &manifest)
+    }
+
+    fn index_manifest(&self, path: &Path, manifest: &Manifest) -> Result<(), Error> {
+        let mut binaries = Vec::new();
+        for package in manifest.find_all_downloads()? {
+            for (_, binary) in package.tarballs {
+                if let Ok(relative) = dist_path(&binary.url, DEFAULT_DIST_SERVER) {
+                    binaries.push((relative.to_string(), binary));
+                }
+            }
+        }
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.binaries.extend(binaries);
+        index.manifests.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Finds the manifest entry for a binary, consulting cached manifests for
+    /// the same date and then the upstream dated manifests
+    fn find_binary(&self, path: &str, date: &str) -> Result<Option<RemoteBinary>, Error> {
+        if let Some(binary) = self.lookup(path) {
+            return Ok(Some(binary));
+        }
+        let date_dir = self.cache.join("dist").join(date);
+        if let Ok(entries) = std::fs::read_dir(&date_dir) {
+            for entry in entries {
+                let manifest_path = entry?.path();
+                let is_manifest = manifest_path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| Artefact::classify(&format!("dist/{}/{}", date, n)) == Artefact::DatedManifest);
+                let indexed = self
+                    .index
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .manifests
+                    .contains(&manifest_path);
+                if is_manifest && !indexed {
+                    let text = std::fs::read_to_string(&manifest_path)?;
+                    self.index_manifest(&manifest_path, &Manifest::try_from(text.as_str())?)?;
+                }
+            }
+        }
+        if let Some(binary) = self.lookup(path) {
+            return Ok(Some(binary));
+        }
+        for channel in LOOKUP_CHANNELS {
+            let manifest_path = format!("dist/{}/channel-rust-{}.toml", date, channel);
+            let local = self.cache.join(&manifest_path);
+            if local.is_file() {
+                continue;
+            }
+            if let Some(text) = self.fetch_manifest(&manifest_path)? {
+                self.store_manifest(&local, &text)?;
+                if let Some(binary) = self.lookup(path) {
+                    return Ok(Some(binary));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup(&self, path: &str) -> Option<RemoteBinary> {
+        let index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.binaries.get(path).cloned()
+    }
+}
+
+/// Whether a cached file was written less than `ttl` ago
+fn is_fresh(path: &Path, ttl: Duration) -> Result<bool, Error> {
+    let modified = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.modified()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+    Ok(age < ttl)
+}
+
+impl<F: Fetcher + Send + Sync> DistSource for CachingProxy<F> {
+    fn open(&self, path: &str) -> Result<Option<PathBuf>, Error> {
+        self.get(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::DirectoryFetcher;
+    use crate::testing::{manifest_text, publish, publish_manifest, temp_dir, TARBALL};
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    const CHANNEL_MANIFEST: &str = "dist/channel-rust-nightly.toml";
+
+    fn publish_nightly(root: &Path, day: u32, latest: bool) {
+        let date = format!("2023-06-{:02}", day);
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let text = manifest_text(&date, "1.72.0-nightly", commit, HOST, &[("clippy", true)]);
+        publish_manifest(root, "nightly", &text, latest);
+    }
+
+    fn proxy(dir: &Path, manifest_ttl: Duration) -> CachingProxy<DirectoryFetcher> {
+        let upstream = ManifestFetcher::new(DirectoryFetcher::new(dir.join("upstream"), DEFAULT_DIST_SERVER));
+        CachingProxy::new(dir.join("cache"), upstream).with_manifest_ttl(manifest_ttl)
+    }
+
+    fn served_date(proxy: &CachingProxy<DirectoryFetcher>) -> String {
+        let path = proxy.get(CHANNEL_MANIFEST).unwrap().unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        Manifest::try_from(text.as_str()).unwrap().get_date().to_string()
+    }
+
+    #[test]
+    fn classifies_paths() {
+        assert_eq!(
+            Artefact::classify("dist/channel-rust-stable.toml"),
+            Artefact::ChannelManifest
+        );
+        assert_eq!(
+            Artefact::classify("dist/2023-06-01/channel-rust-nightly.toml"),
+            Artefact::DatedManifest
+        );
+        assert_eq!(
+            Artefact::classify("dist/2023-06-01/clippy-nightly-x86_64-unknown-linux-gnu.tar.xz"),
+            Artefact::Binary { date: "2023-06-01" }
+        );
+        assert_eq!(Artefact::classify("dist/rustc-1.70.0-src.tar.gz"), Artefact::Other);
+        assert_eq!(
+            Artefact::classify("dist/latest/channel-rust-stable.toml"),
+            Artefact::Other
+        );
+        assert_eq!(Artefact::classify("rustup/release-stable.toml"), Artefact::Other);
+    }
+
+    #[test]
+    fn revalidates_channel_manifests_after_ttl() {
+        let dir = temp_dir("proxy-ttl");
+        let upstream = dir.join("upstream");
+        publish_nightly(&upstream, 1, true);
+        let proxy = proxy(&dir, Duration::from_millis(500));
+        assert_eq!(served_date(&proxy), "2023-06-01");
+        assert!(dir.join("cache/dist/2023-06-01/channel-rust-nightly.toml").is_file());
+
+        publish_nightly(&upstream, 2, true);
+        assert_eq!(served_date(&proxy), "2023-06-01");
+
+        // Once the TTL has passed only the digest is revalidated, and the
+        // manifest requested after it matches
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(served_date(&proxy), "2023-06-01");
+        let digest = proxy.get(&format!("{}.sha256", CHANNEL_MANIFEST)).unwrap().unwrap();
+        assert_eq!(served_date(&proxy), "2023-06-02");
+        let text = std::fs::read(proxy.get(CHANNEL_MANIFEST).unwrap().unwrap()).unwrap();
+        assert!(matches_digest_file(&std::fs::read_to_string(digest).unwrap(), &text));
+
+        // The manifest is revalidated by itself after twice the TTL
+        publish_nightly(&upstream, 3, true);
+        std::thread::sleep(Duration::from_millis(1100));
+        assert_eq!(served_date(&proxy), "2023-06-03");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn serves_stale_manifests_when_upstream_fails() {
+        let dir = temp_dir("proxy-stale");
+        let upstream = dir.join("upstream");
+        publish_nightly(&upstream, 1, true);
+        let proxy = proxy(&dir, Duration::ZERO);
+        assert_eq!(served_date(&proxy), "2023-06-01");
+
+        publish_nightly(&upstream, 2, true);
+        std::fs::write(
+            upstream.join(format!("{}.sha256", CHANNEL_MANIFEST)),
+            "0000  channel-rust-nightly.toml",
+        )
+        .unwrap();
+        assert_eq!(served_date(&proxy), "2023-06-01");
+
+        std::fs::remove_dir_all(dir.join("cache")).unwrap();
+        assert!(matches!(proxy.get(CHANNEL_MANIFEST), Err(Error::DigestMismatch(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fetches_binaries_listed_in_manifests() {
+        let dir = temp_dir("proxy-binaries");
+        let upstream = dir.join("upstream");
+        publish_nightly(&upstream, 1, false);
+        publish_nightly(&upstream, 2, true);
+        let proxy = proxy(&dir, DEFAULT_MANIFEST_TTL);
+
+        // Indexed from the channel manifest, which is no longer upstream
+        proxy.get(CHANNEL_MANIFEST).unwrap().unwrap();
+        std::fs::remove_file(upstream.join("dist/2023-06-02/channel-rust-nightly.toml")).unwrap();
+        let path = proxy
+            .get(&format!("dist/2023-06-02/clippy-{}.tar.xz", HOST))
+            .unwrap()
+            .unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), TARBALL);
+
+        // Found through the upstream manifest for the same date
+        let path = proxy
+            .get(&format!("dist/2023-06-01/clippy-{}.tar.xz", HOST))
+            .unwrap()
+            .unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), TARBALL);
+        assert!(dir.join("cache/dist/2023-06-01/channel-rust-nightly.toml").is_file());
+
+        // Binaries which are not listed are neither fetched nor cached
+        publish(&upstream, &format!("dist/2023-06-01/rustfmt-{}.tar.xz", HOST), TARBALL);
+        assert!(proxy
+            .get(&format!("dist/2023-06-01/rustfmt-{}.tar.xz", HOST))
+            .unwrap()
+            .is_none());
+
+        // Nor are binaries which fail verification
+        publish(&upstream, &format!("dist/2023-06-02/rust-{}.tar.xz", HOST), b"tampered");
+        assert!(proxy.get(&format!("dist/2023-06-02/rust-{}.tar.xz", HOST)).is_err());
+        assert!(!dir.join(format!("cache/dist/2023-06-02/rust-{}.tar.xz", HOST)).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+use crate::history::ManifestHistory;
+use crate::SupportedTarget;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+/// The availability of a component on each date of a report
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ComponentAvailability {
+    /// The component name
+    pub component: String,
+
+    /// Whether the component was available on each date of the report, or
+    /// `None` if there was no manifest for the date
+    pub available: Vec<Option<bool>>,
+
+    /// The last date in the history on which the component was available
+    pub last_available: Option<NaiveDate>,
+}
+
+/// The availability of components for one target
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TargetAvailability {
+    /// The target triple
+    pub target: String,
+
+    /// The availability of each component, sorted by name
+    pub components: Vec<ComponentAvailability>,
+
+    /// The last date in the history on which every component was available
+    pub last_complete: Option<NaiveDate>,
+}
+
+/// A report of component availability over recent dates for a set of targets,
+/// like the rustup components history site
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AvailabilityReport {
+    /// The channel the report is for
+    pub channel: String,
+
+    /// The dates covered by the report, newest first
+    pub dates: Vec<NaiveDate>,
+
+    /// The availability for each target, sorted by target
+    pub targets: Vec<TargetAvailability>,
+}
+
+impl AvailabilityReport {
+    /// Generates a report covering `days` days up to the newest date in the
+    /// history.
+    ///
+    /// If `targets` is empty, every host the toolchain was available for
+    /// during the period is reported on. If `components` is empty, every
+    /// component available for a target during the period is reported on.
+    #[must_use]
+    pub fn new(
+        history: &ManifestHistory,
+        days: u32,
+        targets: &[SupportedTarget],
+        components: &[String],
+    ) -> AvailabilityReport {
+        let history_dates = history.dates();
+        let dates: Vec<NaiveDate> = history_dates
+            .last()
+            .map(|newest| newest.iter_days().rev().take(days as usize).collect())
+            .unwrap_or_default();
+        let available = |date: NaiveDate, component: &str, target: &SupportedTarget| {
+            history.is_available(date, component, target).map(|available| {
+                available || history.is_available(date, component, &SupportedTarget::Independent) == Some(true)
+            })
+        };
+        let in_period = |date: &NaiveDate| dates.contains(date);
+
+        let targets: Vec<SupportedTarget> = if targets.is_empty() {
+            history
+                .targets_for_package("rust")
+                .into_iter()
+                .filter(|target| history.available_dates("rust", target).iter().any(in_period))
+                .collect()
+        } else {
+            targets.to_vec()
+        };
+
+        let mut target_reports = Vec::with_capacity(targets.len());
+        for target in &targets {
+            let names: BTreeSet<String> = if components.is_empty() {
+                history
+                    .packages_for_target(target)
+                    .into_iter()
+                    .chain(history.packages_for_target(&SupportedTarget::Independent))
+                    .filter(|package| {
+                        package != "rust"
+                            && history
+                                .available_dates(package, target)
+                                .iter()
+                                .chain(&history.available_dates(package, &SupportedTarget::Independent))
+                                .any(in_period)
+                    })
+                    .collect()
+            } else {
+                components.iter().cloned().collect()
+            };
+            let component_reports: Vec<ComponentAvailability> = names
+                .iter()
+                .map(|component| ComponentAvailability {
+                    component: component.clone(),
+                    available: dates.iter().map(|date| available(*date, component, target)).collect(),
+                    last_available: history_dates
+                        .iter()
+                        .rev()
+                        .find(|date| available(**date, component, target) == Some(true))
+                        .copied(),
+                })
+                .collect();
+            let last_complete = history_dates
+                .iter()
+                .rev()
+                .find(|date| {
+                    names
+                        .iter()
+                        .all(|component| available(**date, component, target) == Some(true))
+                })
+                .copied();
+            target_reports.push(TargetAvailability {
+                target: target.to_string(),
+                components: component_reports,
+                last_complete,
+            });
+        }
+        target_reports.sort_by(|a, b| a.target.cmp(&b.target));
+
+        AvailabilityReport {
+            channel: history.channel().to_string(),
+            dates,
+            targets: target_reports,
+        }
+    }
+
+    /// Renders the report as a standalone HTML page
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!("Rust {} component history", escape(&self.channel));
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            title, STYLE, title
+        );
+        for target in &self.targets {
+            let _ = writeln!(html, "<h2 id=\"{0}\">{0}</h2>", escape(&target.target));
+            let last_complete = target
+                .last_complete
+                .map_or_else(|| "never".to_string(), |date| date.to_string());
+            let _ = writeln!(
+                html,
+                "<p>Last date with all components: <strong>{}</strong></p>",
+                last_complete
+            );
+            html.push_str("<table>\n<tr><th>Component</th>");
+            for date in &self.dates {
+                let _ = write!(html, "<th>{}</th>", date);
+            }
+            html.push_str("<th>Last available</th></tr>\n");
+            for component in &target.components {
+                let _ = write!(html, "<tr><th>{}</th>", escape(&component.component));
+                for available in &component.available {
+                    html.push_str(match available {
+                        Some(true) => "<td class=\"available\">present</td>",
+                        Some(false) => "<td class=\"missing\">missing</td>",
+                        None => "<td class=\"unknown\"></td>",
+                    });
+                }
+                let last_available = component
+                    .last_available
+                    .map(|date| date.to_string())
+                    .unwrap_or_default();
+                let _ = writeln!(html, "<td>{}</td></tr>", last_available);
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+const STYLE: &str = "body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 6px; text-align: center; }
+td.available { background: #cfc; }
+td.missing { background: #fcc; }
+td.unknown { background: #eee; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::manifest_text;
+    use crate::Manifest;
+    use std::str::FromStr;
+
+    const HOST: &str = "x86_64-unknown-linux-gnu";
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, day).unwrap()
+    }
+
+    /// Records nightlies for the 1st, 2nd and 4th of June 2023. Clippy is
+    /// available on the 1st and 2nd and rustfmt on the 1st and 4th.
+    fn history() -> ManifestHistory {
+        let mut history = ManifestHistory::new("nightly");
+        for (day, clippy, rustfmt) in [(1, true, true), (2, true, false), (4, false, true)] {
+            let commit = "871b5952023139738f72eba235063575062bc2e9";
+            let text = manifest_text(
+                &date(day).to_string(),
+                "1.72.0-nightly",
+                commit,
+                HOST,
+                &[("clippy", clippy), ("rustfmt", rustfmt)],
+            );
+            history.add_manifest(&Manifest::try_from(text.as_str()).unwrap());
+        }
+        history
+    }
+
+    #[test]
+    fn reports_availability_grid() {
+        let report = AvailabilityReport::new(&history(), 3, &[], &[]);
+        let expected = AvailabilityReport {
+            channel: "nightly".to_string(),
+            dates: vec![date(4), date(3), date(2)],
+            targets: vec![TargetAvailability {
+                target: HOST.to_string(),
+                components: vec![
+                    ComponentAvailability {
+                        component: "clippy".to_string(),
+                        available: vec![Some(false), None, Some(true)],
+                        last_available: Some(date(2)),
+                    },
+                    ComponentAvailability {
+                        component: "rustfmt".to_string(),
+                        available: vec![Some(true), None, Some(false)],
+                        last_available: Some(date(4)),
+                    },
+                ],
+                last_complete: Some(date(1)),
+            }],
+        };
+        assert_eq!(report, expected);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"available\":[false,null,true]"));
+        assert_eq!(serde_json::from_str::<AvailabilityReport>(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn reports_requested_components_and_targets() {
+        let host = SupportedTarget::from_str(HOST).unwrap();
+        let report = AvailabilityReport::new(&history(), 2, &[host], &["clippy".to_string(), "miri".to_string()]);
+        assert_eq!(report.dates, [date(4), date(3)]);
+        let target = &report.targets[0];
+        let names: Vec<&str> = target.components.iter().map(|c| c.component.as_str()).collect();
+        assert_eq!(names, ["clippy", "miri"]);
+        assert_eq!(target.components[1].available, [Some(false), None]);
+        assert_eq!(target.components[1].last_available, None);
+        assert_eq!(target.last_complete, None);
+    }
+
+    #[test]
+    fn renders_html() {
+        let html = AvailabilityReport::new(&history(), 3, &[], &[]).to_html();
+        assert!(html.contains("<title>Rust nightly component history</title>"));
+        assert!(html.contains(&format!("<h2 id=\"{0}\">{0}</h2>", HOST)));
+        assert!(html.contains("Last date with all components: <strong>2023-06-01</strong>"));
+        assert!(html.contains("<th>2023-06-04</th><th>2023-06-03</th><th>2023-06-02</th>"));
+        assert!(html.contains(
+            "<tr><th>clippy</th><td class=\"missing\">missing</td><td class=\"unknown\"></td>\
+             <td class=\"available\">present</td><td>2023-06-02</td></tr>"
+        ));
+    }
+
+    #[test]
+    fn escapes_html() {
+        let report = AvailabilityReport {
+            channel: "a&b".to_string(),
+            dates: vec![date(1)],
+            targets: vec![TargetAvailability {
+                target: "x86_64-\"evil\"".to_string(),
+                components: vec![ComponentAvailability {
+                    component: "<script>".to_string(),
+                    available: vec![Some(true)],
+                    last_available: Some(date(1)),
+                }],
+                last_complete: None,
+            }],
+        };
+        let html = report.to_html();
+        assert!(html.contains("<title>Rust a&amp;b component history</title>"));
+        assert!(html.contains("<h2 id=\"x86_64-&quot;evil&quot;\">x86_64-&quot;evil&quot;</h2>"));
+        assert!(html.contains("<tr><th>&lt;script&gt;</th>"));
+        assert!(html.contains("<strong>never</strong>"));
+        assert!(!html.contains("<script>") && !html.contains("\"evil\""));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::fetch::local_path;
+use crate::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// The maximum size of a request head we are willing to read
+const MAX_REQUEST_HEAD: u64 = 16 * 1024;
+
+/// How long a connection may wait on a read or write before it is closed,
+/// unless configured otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of connections handled at once, unless configured otherwise
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// How long to wait after failing to accept a connection before trying again,
+/// so that running out of file descriptors does not busy-loop
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// A minimal HTTP server which serves a mirror directory using the same URL
+/// layout as `static.rust-lang.org`, so that it can be used as
+/// `RUSTUP_DIST_SERVER` or as a stand-in for the official server in tests.
+pub struct DistServer {
+    listener: TcpListener,
+    source: Arc<dyn DistSource>,
+    timeout: Duration,
+    max_connections: usize,
+}
+
+impl std::fmt::Debug for DistServer {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        formatter
+            .debug_struct("DistServer")
+            .field("listener", &self.listener)
+            .field("timeout", &self.timeout)
+            .field("max_connections", &self.max_connections)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A source of the files served by a `DistServer`
+pub trait DistSource: Send + Sync {
+    /// Returns the local file corresponding to a path relative to the root of
+    /// the distribution server, or `None` if there is no such file.
+    fn open(&self, path: &str) -> Result<Option<PathBuf>, Error>;
+}
+
+/// Serves the files in a directory
+#[derive(Clone, Debug)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    /// Constructs a source serving the specified directory
+    pub fn new<P: Into<PathBuf>>(root: P) -> DirectorySource {
+        DirectorySource { root: root.into() }
+    }
+}
+
+impl DistSource for DirectorySource {
+    fn open(&self, path: &str) -> Result<Option<PathBuf>, Error> {
+        Ok(local_path(&self.root, path).filter(|path| path.is_file()))
+    }
+}
+
+impl DistServer {
+    /// Binds a server for the specified directory to the specified address.
+    /// Binding to port 0 selects an unused port.
+    pub fn bind<A: ToSocketAddrs, P: Into<PathBuf>>(address: A, root: P) -> Result<DistServer, Error> {
+        Self::bind_with_source(address, DirectorySource::new(root))
+    }
+
+    /// Binds a server for the specified source to the specified address
+    pub fn bind_with_source<A: ToSocketAddrs, S: DistSource + 'static>(
+        address: A,
+        source: S,
+    ) -> Result<DistServer, Error> {
+        let listener = TcpListener::bind(address)?;
+        Ok(DistServer {
+            listener,
+            source: Arc::new(source),
+            timeout: DEFAULT_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+        })
+    }
+
+    /// Sets how long a connection may wait on a read or write before it is
+    /// closed
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> DistServer {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of connections handled at once. Further connections
+    /// wait to be accepted until one finishes.
+    #[must_use]
+    pub fn with_max_connections(mut self, max_connections: usize) -> DistServer {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// The address the server is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// The URL to use as the distribution server when connecting to this server
+    pub fn base_url(&self) -> Result<String, Error> {
+        Ok(format!("http://{}", self.local_addr()?))
+    }
+
+    /// Serves requests indefinitely. Each connection is handled on its own
+    /// thread, up to the connection limit. Failures to accept a connection
+    /// (e.g. because the process has run out of file descriptors) are logged
+    /// to standard error and do not stop the server.
+    pub fn run(&self) -> Result<(), Error> {
+        let slots = Arc::new(ConnectionSlots::new(self.max_connections));
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    std::thread::sleep(ACCEPT_RETRY_DELAY);
+                    continue;
+                }
+            };
+            slots.acquire();
+            let source = Arc::clone(&self.source);
+            let slots = Arc::clone(&slots);
+            let timeout = self.timeout;
+            std::thread::spawn(move || {
+                // Errors here only affect a single client, which will observe
+                // the closed connection.
+                let _ = stream
+                    .set_read_timeout(Some(timeout))
+                    .and_then(|()| stream.set_write_timeout(Some(timeout)))
+                    .map_err(Error::from)
+                    .and_then(|()| handle_connection(stream, source.as_ref()));
+                slots.release();
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves requests on a background thread
+    #[must_use]
+    pub fn spawn(self) -> std::thread::JoinHandle<Result<(), Error>> {
+        std::thread::spawn(move || self.run())
+    }
+}
+
+/// Counts the connections being handled, blocking new ones at the limit
+#[derive(Debug)]
+struct ConnectionSlots {
+    active: Mutex<usize>,
+    released: Condvar,
+    limit: usize,
+}
+
+impl ConnectionSlots {
+    fn new(limit: usize) -> ConnectionSlots {
+        ConnectionSlots {
+            active: Mutex::new(0),
+            released: Condvar::new(),
+            limit,
+        }
+    }
+
+    fn acquire(&self) {
+        let mut active = self.active.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        while *active >= self.limit {
+            active = self
+                .released
+                .wait(active)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+        *active += 1;
+    }
+
+    fn release(&self) {
+        let mut active = self.active.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        *active -= 1;
+        self.released.notify_one();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Method {
+    Get,
+    Head,
+}
+
+fn handle_connection(stream: TcpStream, source: &dyn DistSource) -> Result<(), Error> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let Some((method, target)) = read_request_head(&mut reader)? else {
+        write_status(&mut stream, 400, "Bad Request")?;
+        // Closing with unread input would reset the connection and could
+        // discard the response, so the rest of the request is read first
+        stream.shutdown(Shutdown::Write)?;
+        std::io::copy(&mut reader.take(MAX_REQUEST_HEAD), &mut std::io::sink())?;
+        return Ok(());
+    };
+    let method = match method.as_str() {
+        "GET" => Method::Get,
+        "HEAD" => Method::Head,
+        _ => return write_status(&mut stream, 405, "Method Not Allowed"),
+    };
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = match path.strip_prefix('/').map(|p| source.open(p)) {
+        Some(Ok(Some(path))) => path,
+        Some(Ok(None)) | None => return write_status(&mut stream, 404, "Not Found"),
+        Some(Err(_)) => return write_status(&mut stream, 502, "Bad Gateway"),
+    };
+    let file = match std::fs::File::open(&path) {
+        Ok(file) if file.metadata()?.is_file() => file,
+        Ok(_) => return write_status(&mut stream, 404, "Not Found"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return write_status(&mut stream, 404, "Not Found"),
+        Err(e) => return Err(e.into()),
+    };
+    let length = file.metadata()?.len();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        content_type(&path),
+        length
+    )?;
+    if method == Method::Get {
+        std::io::copy(&mut file.take(length), &mut stream)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads the request line and headers, returning the method and request
+/// target. Returns `None` if the request is malformed or its head is too large.
+fn read_request_head<R: BufRead>(reader: &mut R) -> Result<Option<(String, String)>, Error> {
+    let mut reader = reader.take(MAX_REQUEST_HEAD);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    if !request_line.ends_with('\n') {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    // Headers are not needed, but must be consumed before responding
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 && reader.limit() == 0 {
+            return Ok(None);
+        }
+        if read == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+    Ok(Some((method.to_string(), target.to_string())))
+}
+
+fn write_status<W: Write>(stream: &mut W, code: u16, reason: &str) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        reason.len(),
+        reason
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => "application/toml",
+        Some("sha256" | "asc") => "text/plain",
+        Some("gz") => "application/gzip",
+        Some("xz") => "application/x-xz",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn request(address: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_files_from_directory() {
+        let root = temp_dir("server");
+        std::fs::create_dir_all(root.join("dist")).unwrap();
+        std::fs::write(root.join("dist/notes.txt"), "hello").unwrap();
+        let server = DistServer::bind("127.0.0.1:0", &root).unwrap();
+        let address = server.local_addr().unwrap();
+        let _ = server.spawn();
+
+        let response = request(address, "GET /dist/notes.txt HTTP/1.1\r\nHost: test\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("Content-Length: 5\r\n") && response.ends_with("\r\n\r\nhello"));
+        let response = request(address, "HEAD /dist/notes.txt HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n") && response.ends_with("\r\n\r\n"));
+        for (path, status) in [
+            ("/dist/absent.txt", "404"),
+            ("/dist", "404"),
+            ("/dist/../../etc/passwd", "404"),
+        ] {
+            let response = request(address, &format!("GET {} HTTP/1.1\r\n\r\n", path));
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {} ", status)),
+                "{}: {}",
+                path,
+                response
+            );
+        }
+        let response = request(address, "POST /dist/notes.txt HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 "));
+        let response = request(address, &format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(20 * 1024)));
+        assert!(response.starts_with("HTTP/1.1 400 "));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn serves_manifests_to_http_fetcher() {
+        use crate::fetch::{HttpFetcher, ManifestFetcher};
+        use crate::testing::{manifest_text, publish_manifest};
+        use crate::Toolchain;
+        use std::str::FromStr;
+
+        let root = temp_dir("server-http");
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let text = manifest_text(
+            "2023-06-01",
+            "1.72.0-nightly",
+            commit,
+            "x86_64-unknown-linux-gnu",
+            &[("clippy", true)],
+        );
+        publish_manifest(&root, "nightly", &text, true);
+        let server = DistServer::bind("127.0.0.1:0", &root).unwrap();
+        let fetcher = ManifestFetcher::with_dist_server(HttpFetcher::new(), &server.base_url().unwrap());
+        let _ = server.spawn();
+
+        let toolchain = Toolchain::from_str("nightly").unwrap();
+        assert_eq!(fetcher.fetch_verified_manifest_text(&toolchain).unwrap(), Some(text));
+        let manifest = fetcher.fetch_manifest(&toolchain).unwrap().unwrap();
+        for package in manifest.find_all_downloads().unwrap() {
+            fetcher.fetch_binary(&package.tarballs[0].1).unwrap();
+        }
+        let toolchain = Toolchain::from_str("beta").unwrap();
+        assert!(fetcher.fetch_manifest_text(&toolchain).unwrap().is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
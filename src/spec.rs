@@ -0,0 +1,287 @@
+use crate::Manifest;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::str::FromStr;
+use target_lexicon::Triple;
+use thiserror::Error;
+
+/// Errors which can occur while parsing a component name
+#[derive(Clone, Debug, Error)]
+pub enum ParseError {
+    /// The name was empty or contained invalid characters
+    #[error("Invalid component name: {0:?}")]
+    InvalidName(String),
+}
+
+/// The name of a component to install, optionally for a specific target, as
+/// written `<package>` or `<package>-<triple>` (e.g. `clippy` or
+/// `rust-std-wasm32-unknown-unknown`). Old package names which have since
+/// been renamed are accepted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ComponentName {
+    /// The package name
+    pub package: String,
+
+    /// The target, or `None` for the host (or a target-independent package)
+    pub target: Option<Triple>,
+}
+
+impl ComponentName {
+    /// Constructs a component name for the host
+    #[must_use]
+    pub fn new(package: &str) -> ComponentName {
+        ComponentName {
+            package: package.to_string(),
+            target: None,
+        }
+    }
+
+    /// Constructs a component name for a specific target
+    #[must_use]
+    pub fn for_target(package: &str, target: Triple) -> ComponentName {
+        ComponentName {
+            package: package.to_string(),
+            target: Some(target),
+        }
+    }
+}
+
+impl FromStr for ComponentName {
+    type Err = ParseError;
+
+    /// Parses a component name. A suffix is taken to be a target if it has at
+    /// least two hyphen-separated parts and parses as a triple; the earliest
+    /// such split is used, so the target is as long as possible.
+    fn from_str(string: &str) -> Result<ComponentName, ParseError> {
+        let valid = !string.is_empty()
+            && string
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(ParseError::InvalidName(string.to_string()));
+        }
+        for (index, _) in string.match_indices('-') {
+            let (package, target) = (&string[..index], &string[index + 1..]);
+            if package.is_empty() || !target.contains('-') {
+                continue;
+            }
+            if let Ok(target) = Triple::from_str(target) {
+                return Ok(ComponentName::for_target(package, target));
+            }
+        }
+        Ok(ComponentName::new(string))
+    }
+}
+
+impl std::fmt::Display for ComponentName {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}", self.package)?;
+        if let Some(target) = &self.target {
+            write!(formatter, "-{}", target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for ComponentName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> Deserialize<'a> for ComponentName {
+    fn deserialize<D>(deserializer: D) -> Result<ComponentName, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let s = String::deserialize(deserializer)?;
+        ComponentName::from_str(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes a set of triples as strings
+mod triples {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashSet;
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+
+    pub fn serialize<S>(triples: &HashSet<Triple>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut names: Vec<String> = triples.iter().map(ToString::to_string).collect();
+        names.sort();
+        serializer.collect_seq(names)
+    }
+
+    pub fn deserialize<'a, D>(deserializer: D) -> Result<HashSet<Triple>, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|name| Triple::from_str(name).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// An install specification for a Rust toolchain
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct InstallSpec {
+    /// The toolchain profile
+    pub profile: String,
+
+    /// The components to be installed (in addition to those implied by the
+    /// profile)
+    pub components: HashSet<ComponentName>,
+
+    /// Architectures for which the Rust standard library should be installed
+    #[serde(with = "triples")]
+    pub targets: HashSet<Triple>,
+}
+
+/// A problem with an install specification found by `InstallSpec::validate`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpecIssue {
+    /// The toolchain is not available for the host, so nothing else could be
+    /// checked
+    UnknownHost(Triple),
+
+    /// The profile does not exist in the manifest
+    UnknownProfile(String),
+
+    /// The component is not known on the host
+    UnknownComponent(ComponentName),
+
+    /// The standard library is not known for the target
+    UnknownTarget(Triple),
+}
+
+impl std::fmt::Display for SpecIssue {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SpecIssue::UnknownHost(host) => write!(formatter, "unknown host {}", host),
+            SpecIssue::UnknownProfile(profile) => write!(formatter, "unknown profile {}", profile),
+            SpecIssue::UnknownComponent(component) => write!(formatter, "unknown component {}", component),
+            SpecIssue::UnknownTarget(target) => write!(formatter, "unknown target {}", target),
+        }
+    }
+}
+
+impl InstallSpec {
+    /// Constructs a specification for a profile with no additional
+    /// components or targets
+    #[must_use]
+    pub fn new(profile: &str) -> InstallSpec {
+        InstallSpec {
+            profile: profile.to_string(),
+            components: HashSet::new(),
+            targets: HashSet::new(),
+        }
+    }
+
+    /// Adds a component to install
+    #[must_use]
+    pub fn with_component(mut self, component: ComponentName) -> InstallSpec {
+        self.components.insert(component);
+        self
+    }
+
+    /// Adds a target to install the standard library for
+    #[must_use]
+    pub fn with_target(mut self, target: Triple) -> InstallSpec {
+        self.targets.insert(target);
+        self
+    }
+
+    /// Checks the profile, components and targets against a manifest for
+    /// installation on a host, returning every problem found. Components
+    /// and targets which are known but currently unavailable are not
+    /// problems.
+    #[must_use]
+    pub fn validate(&self, manifest: &Manifest, host: &Triple) -> Vec<SpecIssue> {
+        if !manifest.get_hosts().contains(host) {
+            return vec![SpecIssue::UnknownHost(host.clone())];
+        }
+        let mut issues = Vec::new();
+        if manifest.get_profile_components(&self.profile).is_none() {
+            issues.push(SpecIssue::UnknownProfile(self.profile.clone()));
+        }
+        let mut components: Vec<&ComponentName> = self.components.iter().collect();
+        components.sort_by_cached_key(ToString::to_string);
+        for component in components {
+            if manifest
+                .resolve_component_name_to_package(host, &component.to_string())
+                .is_err()
+            {
+                issues.push(SpecIssue::UnknownComponent(component.clone()));
+            }
+        }
+        let mut targets: Vec<&Triple> = self.targets.iter().collect();
+        targets.sort_by_cached_key(ToString::to_string);
+        for target in targets {
+            let std = ComponentName::for_target("rust-std", target.clone());
+            if manifest
+                .resolve_component_name_to_package(host, &std.to_string())
+                .is_err()
+            {
+                issues.push(SpecIssue::UnknownTarget(target.clone()));
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        let spec = InstallSpec::new("minimal")
+            .with_component(ComponentName::new("clippy"))
+            .with_component(ComponentName::from_str("rust-std-wasm32-unknown-unknown").unwrap())
+            .with_target(Triple::from_str("aarch64-unknown-linux-gnu").unwrap())
+            .with_target(Triple::from_str("wasm32-unknown-unknown").unwrap());
+        let json = serde_json::to_value(&spec).unwrap();
+        assert_eq!(
+            json["targets"],
+            serde_json::json!(["aarch64-unknown-linux-gnu", "wasm32-unknown-unknown"])
+        );
+        assert_eq!(serde_json::from_value::<InstallSpec>(json).unwrap(), spec);
+    }
+
+    #[test]
+    fn splits_component_targets() {
+        let split = |name: &str| {
+            let component = ComponentName::from_str(name).unwrap();
+            (component.package, component.target.map(|t| t.to_string()))
+        };
+        assert_eq!(split("clippy"), ("clippy".to_string(), None));
+        assert_eq!(split("llvm-tools-preview"), ("llvm-tools-preview".to_string(), None));
+        assert_eq!(
+            split("rust-std-wasm32-unknown-unknown"),
+            ("rust-std".to_string(), Some("wasm32-unknown-unknown".to_string()))
+        );
+        assert_eq!(
+            split("rust-analysis-x86_64-unknown-linux-gnu"),
+            (
+                "rust-analysis".to_string(),
+                Some("x86_64-unknown-linux-gnu".to_string())
+            )
+        );
+        assert_eq!(
+            ComponentName::from_str("rust-std-aarch64-apple-darwin")
+                .unwrap()
+                .to_string(),
+            "rust-std-aarch64-apple-darwin"
+        );
+        for invalid in ["", "clippy preview", "rust-std/x86_64-unknown-linux-gnu"] {
+            assert!(ComponentName::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
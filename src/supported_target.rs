@@ -1,3 +1,4 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::str::FromStr;
 use target_lexicon::Triple;
 
@@ -33,6 +34,25 @@ impl FromStr for SupportedTarget {
     }
 }
 
+impl Serialize for SupportedTarget {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> Deserialize<'a> for SupportedTarget {
+    fn deserialize<D>(deserializer: D) -> Result<SupportedTarget, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let s = String::deserialize(deserializer)?;
+        SupportedTarget::from_str(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
 impl SupportedTarget {
     #[must_use]
     pub fn supports(&self, other: &Triple) -> bool {
@@ -42,3 +62,20 @@ impl SupportedTarget {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip() {
+        for target in [
+            SupportedTarget::Independent,
+            SupportedTarget::Dependent(Triple::from_str("x86_64-unknown-linux-gnu").unwrap()),
+        ] {
+            let json = serde_json::to_string(&target).unwrap();
+            assert_eq!(json, format!("\"{}\"", target));
+            assert_eq!(serde_json::from_str::<SupportedTarget>(&json).unwrap(), target);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::fetch::{dist_path, DEFAULT_DIST_SERVER};
+use crate::mirror::sha256_file_contents;
+use crate::Manifest;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// The contents of every binary in test manifests
+pub(crate) const TARBALL: &[u8] = b"test tarball";
+
+/// The SHA-256 digest of `TARBALL`
+const DIGEST: &str = "16ac1ea143cf30263883056bf563bf5984d91a980fae7b224a70b2e2b8fe9dbf";
+
+/// Builds the text of a manifest with a `rust` package for `host` and the
+/// listed packages, each of which is available or not. Every package is a
+/// component of `rust` and of the `default` profile.
+pub(crate) fn manifest_text(
+    date: &str,
+    version: &str,
+    git_commit: &str,
+    host: &str,
+    packages: &[(&str, bool)],
+) -> String {
+    let mut text = format!("manifest-version = \"2\"\ndate = \"{}\"\n", date);
+    let names: Vec<&str> = packages.iter().map(|(name, _)| *name).collect();
+    let components: Vec<String> = names
+        .iter()
+        .map(|name| format!("{{ pkg = \"{}\", target = \"{}\" }}", name, host))
+        .collect();
+    for (name, available) in std::iter::once(&("rust", true)).chain(packages) {
+        let _ = write!(
+            text,
+            "[pkg.{name}]\nversion = \"{version}\"\ngit_commit_hash = \"{git_commit}\"\n\
+             [pkg.{name}.target.{host}]\navailable = {available}\n"
+        );
+        if *available {
+            let _ = writeln!(
+                text,
+                "xz_url = \"https://static.rust-lang.org/dist/{date}/{name}-{host}.tar.xz\"\nxz_hash = \"{DIGEST}\""
+            );
+        }
+        if *name == "rust" {
+            let _ = writeln!(text, "components = [{}]", components.join(", "));
+        }
+    }
+    let quoted: Vec<String> = names.iter().map(|name| format!("\"{}\"", name)).collect();
+    let _ = write!(
+        text,
+        "[profiles]\ndefault = [{}]\n[renames]\n[artifacts]\n",
+        quoted.join(", ")
+    );
+    text
+}
+
+/// Returns an empty directory for a test, named after the test and process
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rtm-{}-test-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a file and its `.sha256` companion at a path relative to the root of
+/// a directory laid out like a distribution server
+pub(crate) fn publish(root: &Path, relative: &str, data: &[u8]) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, data).unwrap();
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    std::fs::write(
+        path.with_file_name(format!("{}.sha256", file_name)),
+        sha256_file_contents(data, file_name),
+    )
+    .unwrap();
+}
+
+/// Publishes a manifest at `dist/<date>/channel-rust-<channel>.toml`, and also
+/// at `dist/channel-rust-<channel>.toml` if `latest` is set, along with every
+/// tarball it lists
+pub(crate) fn publish_manifest(root: &Path, channel: &str, text: &str, latest: bool) {
+    let manifest = Manifest::try_from(text).unwrap();
+    let name = format!("channel-rust-{}.toml", channel);
+    publish(root, &format!("dist/{}/{}", manifest.get_date(), name), text.as_bytes());
+    if latest {
+        publish(root, &format!("dist/{}", name), text.as_bytes());
+    }
+    for package in manifest.find_all_downloads().unwrap() {
+        for (_, binary) in &package.tarballs {
+            publish(root, dist_path(&binary.url, DEFAULT_DIST_SERVER).unwrap(), TARBALL);
+        }
+    }
+}
@@ -1,4 +1,9 @@
+use crate::fetch::DEFAULT_DIST_SERVER;
+use crate::host::{detect_host, HostError, PartialTriple};
+use crate::Manifest;
 use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::str::FromStr;
 use target_lexicon::Triple;
@@ -18,6 +23,51 @@ pub enum Channel {
 
     /// A specific Rust compiler version
     Version(u16, u16, Option<u16>),
+
+    /// The beta channel for a specific version (e.g. `1.72-beta`), or a
+    /// specific beta release (e.g. `1.72.0-beta.3`)
+    VersionBeta {
+        /// The major version
+        major: u16,
+
+        /// The minor version
+        minor: u16,
+
+        /// The patch version
+        patch: Option<u16>,
+
+        /// The beta release number
+        number: Option<u16>,
+    },
+
+    /// The nightly channel for a specific version (e.g. `1.73-nightly`)
+    VersionNightly {
+        /// The major version
+        major: u16,
+
+        /// The minor version
+        minor: u16,
+
+        /// The patch version
+        patch: Option<u16>,
+    },
+
+    /// A channel published by a private distribution server as
+    /// `channel-rust-<name>.toml`, written as `custom:<name>`
+    Custom(String),
+}
+
+fn fmt_version(
+    formatter: &mut std::fmt::Formatter<'_>,
+    major: u16,
+    minor: u16,
+    patch: Option<u16>,
+) -> Result<(), std::fmt::Error> {
+    write!(formatter, "{}.{}", major, minor)?;
+    if let Some(patch) = patch {
+        write!(formatter, ".{}", patch)?;
+    }
+    Ok(())
 }
 
 impl std::fmt::Display for Channel {
@@ -26,12 +76,25 @@ impl std::fmt::Display for Channel {
             Channel::Stable => write!(formatter, "stable"),
             Channel::Beta => write!(formatter, "beta"),
             Channel::Nightly => write!(formatter, "nightly"),
-            Channel::Version(major, minor, None) => {
-                write!(formatter, "{}.{}", major, minor)
+            Channel::Version(major, minor, patch) => fmt_version(formatter, *major, *minor, *patch),
+            Channel::VersionBeta {
+                major,
+                minor,
+                patch,
+                number,
+            } => {
+                fmt_version(formatter, *major, *minor, *patch)?;
+                write!(formatter, "-beta")?;
+                if let Some(number) = number {
+                    write!(formatter, ".{}", number)?;
+                }
+                Ok(())
             }
-            Channel::Version(major, minor, Some(patch)) => {
-                write!(formatter, "{}.{}.{}", major, minor, patch)
+            Channel::VersionNightly { major, minor, patch } => {
+                fmt_version(formatter, *major, *minor, *patch)?;
+                write!(formatter, "-nightly")
             }
+            Channel::Custom(name) => write!(formatter, "{}{}", CUSTOM_PREFIX, name),
         }
     }
 }
@@ -46,6 +109,35 @@ pub enum ChannelParseError {
     /// A version had an invalid number of components
     #[error("Incorrect number of components in version: {0}")]
     InvalidVersionComponentCount(usize),
+
+    /// A version was followed by something other than a beta or nightly
+    /// suffix
+    #[error("Invalid version suffix: {0}")]
+    InvalidVersionSuffix(String),
+
+    /// A custom channel name was empty, contained invalid characters or was
+    /// the name of an official channel
+    #[error("Invalid channel name: {0:?}")]
+    InvalidName(String),
+
+    /// The name was neither an official channel nor a version, and was not
+    /// marked as a custom channel
+    #[error("Unknown channel {0:?} (custom channels are written as {CUSTOM_PREFIX}<name>)")]
+    Unknown(String),
+}
+
+/// The prefix which marks a channel name as that of a custom channel
+const CUSTOM_PREFIX: &str = "custom:";
+
+fn parse_version(string: &str) -> Result<(u16, u16, Option<u16>), ChannelParseError> {
+    let components: Result<Vec<u16>, _> = string.split('.').map(u16::from_str).collect();
+    let components = components.map_err(|_| ChannelParseError::IntegerParse)?;
+    if components.len() < 2 || components.len() > 3 {
+        return Err(ChannelParseError::InvalidVersionComponentCount(components.len()));
+    }
+    assert!(components.len() >= 2);
+    assert!(components.len() <= 3);
+    Ok((components[0], components[1], components.get(2).copied()))
 }
 
 impl FromStr for Channel {
@@ -56,26 +148,164 @@ impl FromStr for Channel {
             "stable" => Ok(Channel::Stable),
             "beta" => Ok(Channel::Beta),
             "nightly" => Ok(Channel::Nightly),
-            _ => {
-                let components: Result<Vec<u16>, _> = string.split('.').map(u16::from_str).collect();
-                let components = components.map_err(|_| ChannelParseError::IntegerParse)?;
-                if components.len() < 2 || components.len() > 3 {
-                    return Err(ChannelParseError::InvalidVersionComponentCount(components.len()));
+            _ if string.starts_with(|c: char| c.is_ascii_digit()) => {
+                let (version, suffix) = match string.split_once('-') {
+                    Some((version, suffix)) => (version, Some(suffix)),
+                    None => (string, None),
+                };
+                let (major, minor, patch) = parse_version(version)?;
+                match suffix {
+                    None => Ok(Channel::Version(major, minor, patch)),
+                    Some("nightly") => Ok(Channel::VersionNightly { major, minor, patch }),
+                    Some("beta") => Ok(Channel::VersionBeta {
+                        major,
+                        minor,
+                        patch,
+                        number: None,
+                    }),
+                    Some(suffix) => {
+                        let number = suffix
+                            .strip_prefix("beta.")
+                            .ok_or_else(|| ChannelParseError::InvalidVersionSuffix(suffix.to_string()))?;
+                        let number = u16::from_str(number).map_err(|_| ChannelParseError::IntegerParse)?;
+                        Ok(Channel::VersionBeta {
+                            major,
+                            minor,
+                            patch,
+                            number: Some(number),
+                        })
+                    }
                 }
-                assert!(components.len() >= 2);
-                assert!(components.len() <= 3);
-                Ok(Channel::Version(
-                    components[0],
-                    components[1],
-                    components.get(2).copied(),
-                ))
             }
+            _ => match string.strip_prefix(CUSTOM_PREFIX) {
+                Some(name) => Channel::custom(name),
+                None => Err(ChannelParseError::Unknown(string.to_string())),
+            },
+        }
+    }
+}
+
+/// The stage of a version's progress towards a stable release
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Stage {
+    Nightly,
+    Beta,
+    Stable,
+}
+
+/// The position of a versioned channel in the release sequence
+struct Release {
+    major: u16,
+    minor: u16,
+    stage: Stage,
+    patch: Option<u16>,
+    number: Option<u16>,
+}
+
+impl Release {
+    fn compare(&self, other: &Release) -> Option<Ordering> {
+        let ordering = (self.major, self.minor, self.stage).cmp(&(other.major, other.minor, other.stage));
+        if ordering.is_ne() {
+            return Some(ordering);
+        }
+        // Within a version, patch and beta release numbers are only ordered if
+        // both are known
+        let optional = |a: Option<u16>, b: Option<u16>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            (None, None) => Some(Ordering::Equal),
+            _ => None,
+        };
+        Some(optional(self.patch, other.patch)?.then(optional(self.number, other.number)?))
+    }
+}
+
+impl Channel {
+    /// Constructs a channel published by a private distribution server as
+    /// `channel-rust-<name>.toml`. The name may only contain ASCII
+    /// alphanumerics, `-` and `_`, and may not be or extend the name of an
+    /// official channel.
+    pub fn custom(name: &str) -> Result<Channel, ChannelParseError> {
+        // Names extending the official channels are rejected, since these are
+        // ambiguous with dated toolchains
+        let official = ["stable", "beta", "nightly"].iter().any(|official| {
+            name.strip_prefix(official)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+        });
+        let valid = !name.is_empty()
+            && !official
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if valid {
+            Ok(Channel::Custom(name.to_string()))
+        } else {
+            Err(ChannelParseError::InvalidName(name.to_string()))
+        }
+    }
+
+    /// The name the channel's manifest is published under, as in
+    /// `channel-rust-<name>.toml`
+    #[must_use]
+    pub fn manifest_name(&self) -> String {
+        match self {
+            Channel::Custom(name) => name.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The release position of a versioned channel. Nightly and beta versions
+    /// without a patch number are always for the `.0` release.
+    fn release(&self) -> Option<Release> {
+        match self {
+            Channel::Version(major, minor, patch) => Some(Release {
+                major: *major,
+                minor: *minor,
+                stage: Stage::Stable,
+                patch: *patch,
+                number: None,
+            }),
+            Channel::VersionBeta {
+                major,
+                minor,
+                patch,
+                number,
+            } => Some(Release {
+                major: *major,
+                minor: *minor,
+                stage: Stage::Beta,
+                patch: Some(patch.unwrap_or_default()),
+                number: *number,
+            }),
+            Channel::VersionNightly { major, minor, patch } => Some(Release {
+                major: *major,
+                minor: *minor,
+                stage: Stage::Nightly,
+                patch: Some(patch.unwrap_or_default()),
+                number: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Channels are ordered where they identify versions: by version number, and
+/// within a version from nightly to beta to stable. `1.70` is not ordered
+/// relative to `1.70.1`, nor `1.72-beta` relative to `1.72.0-beta.3`, and
+/// channels which move over time (e.g. `nightly`) are only equal to
+/// themselves.
+impl PartialOrd for Channel {
+    fn partial_cmp(&self, other: &Channel) -> Option<Ordering> {
+        if self == other {
+            return Some(Ordering::Equal);
         }
+        self.release()?
+            .compare(&other.release()?)
+            .filter(|ordering| ordering.is_ne())
     }
 }
 
 /// Specification of a Rust toolchain (as typically given to Rustup)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Toolchain {
     /// The release channel
     pub channel: Channel,
@@ -88,16 +318,36 @@ pub struct Toolchain {
 }
 
 impl Toolchain {
+    /// Fills in the host with the detected host triple if it is absent
+    #[must_use]
+    pub fn with_detected_host(mut self) -> Toolchain {
+        if self.host.is_none() {
+            self.host = Some(detect_host());
+        }
+        self
+    }
+
     /// Returns the manifest URL for the specified toolchain
     #[must_use]
     pub fn manifest_url(&self) -> String {
+        self.manifest_url_for_server(DEFAULT_DIST_SERVER)
+    }
+
+    /// Returns the manifest URL for the specified toolchain on the specified
+    /// distribution server (e.g. the value of `RUSTUP_DIST_SERVER`)
+    #[must_use]
+    pub fn manifest_url_for_server(&self, dist_server: &str) -> String {
+        format!("{}/{}", dist_server.trim_end_matches('/'), self.manifest_path())
+    }
+
+    /// Returns the path of the manifest relative to the root of a distribution
+    /// server
+    #[must_use]
+    pub fn manifest_path(&self) -> String {
         if let Some(date) = &self.date {
-            format!(
-                "https://static.rust-lang.org/dist/{}/channel-rust-{}.toml",
-                date, self.channel
-            )
+            format!("dist/{}/channel-rust-{}.toml", date, self.channel.manifest_name())
         } else {
-            format!("https://static.rust-lang.org/dist/channel-rust-{}.toml", self.channel)
+            format!("dist/channel-rust-{}.toml", self.channel.manifest_name())
         }
     }
 }
@@ -112,6 +362,10 @@ pub enum ParseError {
     /// The target name was invalid
     #[error("Failed to target: {0}")]
     Target(#[from] target_lexicon::ParseError),
+
+    /// An abbreviated host was not recognised
+    #[error("Failed to parse host: {0}")]
+    Host(#[from] HostError),
 }
 
 fn intersperse_hyphen<I: Iterator<Item = S>, S: AsRef<str>>(iter: I) -> String {
@@ -130,30 +384,150 @@ fn intersperse_hyphen<I: Iterator<Item = S>, S: AsRef<str>>(iter: I) -> String {
     result
 }
 
+/// Splits hyphen-separated components into a channel made up of the first
+/// `channel_components` of them, an optional date, and the rest as the host
+fn split_components(
+    components: &[&str],
+    channel_components: usize,
+) -> Result<(Channel, Option<NaiveDate>, Option<String>), ChannelParseError> {
+    let channel = Channel::from_str(&intersperse_hyphen(components[..channel_components].iter()))?;
+    let mut split: VecDeque<_> = components[channel_components..].iter().collect();
+    let mut date = None;
+    if split.len() >= 3 {
+        let date_candidate = intersperse_hyphen(split.range(0..3));
+        if let Ok(parsed) = NaiveDate::from_str(&date_candidate) {
+            date = Some(parsed);
+            split.drain(0..3);
+        }
+    }
+    let host = if split.is_empty() {
+        None
+    } else {
+        Some(intersperse_hyphen(split.iter()))
+    };
+    Ok((channel, date, host))
+}
+
+/// Parses a toolchain string, trying each number of leading components as
+/// the channel name. Channel names may themselves contain hyphens (e.g.
+/// `1.72.0-beta.3`), so the shortest channel name for which the rest of the
+/// string is a valid date and host is used.
+fn parse_shortest<T>(
+    string: &str,
+    parse_split: impl Fn(&[&str], usize) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let components: Vec<&str> = string.split('-').collect();
+    let mut first_error = None;
+    for channel_components in 1..=components.len() {
+        match parse_split(&components, channel_components) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_error.expect("A toolchain string always has at least one component"))
+}
+
+impl Toolchain {
+    /// Parses a toolchain whose channel is made up of the first
+    /// `channel_components` hyphen-separated components of `components`
+    fn parse_split(components: &[&str], channel_components: usize) -> Result<Toolchain, ParseError> {
+        let (channel, date, host) = split_components(components, channel_components)?;
+        Ok(Toolchain {
+            channel,
+            date,
+            host: host.as_deref().map(Triple::from_str).transpose()?,
+        })
+    }
+}
+
 impl FromStr for Toolchain {
     type Err = ParseError;
 
     fn from_str(string: &str) -> Result<Toolchain, ParseError> {
-        let mut split: VecDeque<_> = string.split('-').collect();
-        let channel = Channel::from_str(split.pop_front().unwrap_or_default())?;
-        let mut result = Toolchain {
-            channel,
-            date: None,
-            host: None,
+        parse_shortest(string, Self::parse_split)
+    }
+}
+
+/// A toolchain name whose host may be abbreviated as Rustup allows (e.g.
+/// `stable-msvc`, `nightly-gnu` or `1.70-x86_64-pc-windows`). The host is
+/// completed with `complete`, against a host such as that from
+/// `host::detect_host`, or
+/// `complete_from`, against the hosts a manifest is available for.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PartialToolchain {
+    /// The release channel
+    pub channel: Channel,
+
+    /// The manifest date
+    pub date: Option<NaiveDate>,
+
+    /// The possibly abbreviated host
+    pub host: Option<PartialTriple>,
+}
+
+impl PartialToolchain {
+    fn parse_split(components: &[&str], channel_components: usize) -> Result<PartialToolchain, ParseError> {
+        let (channel, date, host) = split_components(components, channel_components)?;
+        let host = match host {
+            Some(host) => Some(PartialTriple::from_str(&host).or_else(|e| {
+                Triple::from_str(&host)
+                    .map(|t| PartialTriple::from_triple(&t))
+                    .map_err(|_| e)
+            })?),
+            None => None,
         };
-        if split.len() >= 3 {
-            let date_candidate = intersperse_hyphen(split.range(0..3));
-            if let Ok(date) = NaiveDate::from_str(&date_candidate) {
-                result.date = Some(date);
-                split.drain(0..3);
-            }
+        Ok(PartialToolchain { channel, date, host })
+    }
+
+    fn with_host(&self, host: Option<Triple>) -> Toolchain {
+        Toolchain {
+            channel: self.channel.clone(),
+            date: self.date,
+            host,
         }
-        if !split.is_empty() {
-            let host_candidate = intersperse_hyphen(split.iter());
-            let host = Triple::from_str(&host_candidate)?;
-            result.host = Some(host);
+    }
+
+    /// Completes the host from `host` as Rustup does. An absent host remains
+    /// absent.
+    pub fn complete(&self, host: &Triple) -> Result<Toolchain, HostError> {
+        let completed = self.host.as_ref().map(|partial| partial.complete(host)).transpose()?;
+        Ok(self.with_host(completed))
+    }
+
+    /// Completes the host by choosing among the hosts a manifest is available
+    /// for, preferring those which match `host` in the parts not given. An
+    /// absent host remains absent.
+    pub fn complete_from(&self, host: &Triple, manifest: &Manifest) -> Result<Toolchain, HostError> {
+        let hosts = manifest.get_hosts();
+        let completed = self
+            .host
+            .as_ref()
+            .map(|partial| partial.complete_from(host, &hosts))
+            .transpose()?;
+        Ok(self.with_host(completed))
+    }
+}
+
+impl FromStr for PartialToolchain {
+    type Err = ParseError;
+
+    fn from_str(string: &str) -> Result<PartialToolchain, ParseError> {
+        parse_shortest(string, Self::parse_split)
+    }
+}
+
+impl std::fmt::Display for PartialToolchain {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.channel.fmt(formatter)?;
+        if let Some(date) = &self.date {
+            write!(formatter, "-{}", date)?;
         }
-        Ok(result)
+        if let Some(host) = &self.host {
+            write!(formatter, "-{}", host)?;
+        }
+        Ok(())
     }
 }
 
@@ -169,3 +543,276 @@ impl std::fmt::Display for Toolchain {
         Ok(())
     }
 }
+
+/// Resolves channels which move over time to the version they were on at a
+/// date, relating nightly and beta toolchains to the stable release they will
+/// become
+pub trait ReleaseLookup {
+    /// Returns the versioned channel (e.g. `1.71-nightly`, `1.70.0-beta.3` or
+    /// `1.69.0`) that `channel` was on at `date`, if known
+    fn resolve(&self, channel: &Channel, date: NaiveDate) -> Option<Channel>;
+}
+
+impl Toolchain {
+    /// Compares two toolchains, using `lookup` to find the versions that
+    /// dated `stable`, `beta` and `nightly` toolchains were on. Returns `None`
+    /// where no order can be determined, such as between toolchains for
+    /// different hosts or between an undated and a dated nightly.
+    ///
+    /// Toolchains on the same channel are ordered by date. Otherwise they are
+    /// ordered by version as for `Channel`, then by date. Unlike the
+    /// `PartialOrd` implementation, toolchains which resolve to the same
+    /// stable release (e.g. `1.69.0` and `stable-2023-05-01`) compare equal.
+    #[must_use]
+    pub fn compare_with(&self, other: &Toolchain, lookup: &dyn ReleaseLookup) -> Option<Ordering> {
+        self.compare(other, Some(lookup))
+    }
+
+    fn compare(&self, other: &Toolchain, lookup: Option<&dyn ReleaseLookup>) -> Option<Ordering> {
+        if self.host != other.host {
+            return None;
+        }
+        if self.channel == other.channel {
+            return match (self.date, other.date) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                (None, None) => Some(Ordering::Equal),
+                _ => None,
+            };
+        }
+        let release = |toolchain: &Toolchain| {
+            toolchain.channel.release().or_else(|| {
+                let resolved = lookup?.resolve(&toolchain.channel, toolchain.date?)?;
+                resolved.release()
+            })
+        };
+        let (a, b) = (release(self)?, release(other)?);
+        match a.compare(&b)? {
+            Ordering::Equal if a.stage == Stage::Stable && a.patch.is_some() => Some(Ordering::Equal),
+            Ordering::Equal => Some(self.date?.cmp(&other.date?)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+/// Toolchains are ordered as by `Toolchain::compare_with`, but without any
+/// knowledge of the versions `stable`, `beta` and `nightly` were on, and only
+/// identical toolchains compare equal.
+impl PartialOrd for Toolchain {
+    fn partial_cmp(&self, other: &Toolchain) -> Option<Ordering> {
+        if self == other {
+            return Some(Ordering::Equal);
+        }
+        self.compare(other, None).filter(|ordering| ordering.is_ne())
+    }
+}
+
+impl Serialize for Channel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> Deserialize<'a> for Channel {
+    fn deserialize<D>(deserializer: D) -> Result<Channel, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Channel::from_str(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Toolchain {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> Deserialize<'a> for Toolchain {
+    fn deserialize<D>(deserializer: D) -> Result<Toolchain, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Toolchain::from_str(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::manifest_text;
+    use std::fmt::Write as _;
+
+    #[test]
+    fn channel_serde_round_trip() {
+        for name in [
+            "stable",
+            "beta",
+            "nightly",
+            "1.70",
+            "1.70.1",
+            "1.72-beta",
+            "1.72.0-beta.3",
+            "1.73-nightly",
+        ] {
+            let channel = Channel::from_str(name).unwrap();
+            let json = serde_json::to_string(&channel).unwrap();
+            assert_eq!(json, format!("\"{}\"", name));
+            assert_eq!(serde_json::from_str::<Channel>(&json).unwrap(), channel);
+        }
+    }
+
+    #[test]
+    fn toolchain_serde_round_trip() {
+        for name in [
+            "nightly",
+            "nightly-2023-06-01",
+            "1.70-x86_64-unknown-linux-gnu",
+            "beta-2023-06-01-aarch64-apple-darwin",
+        ] {
+            let toolchain = Toolchain::from_str(name).unwrap();
+            let json = serde_json::to_string(&toolchain).unwrap();
+            assert_eq!(json, format!("\"{}\"", name));
+            assert_eq!(serde_json::from_str::<Toolchain>(&json).unwrap(), toolchain);
+        }
+        assert!(serde_json::from_str::<Toolchain>("\"nightly-2023-06-01-nonsense\"").is_err());
+    }
+
+    #[test]
+    fn parses_channels() {
+        assert_eq!(Channel::from_str("1.70").unwrap(), Channel::Version(1, 70, None));
+        assert_eq!(
+            Channel::from_str("1.72.0-beta.3").unwrap(),
+            Channel::VersionBeta {
+                major: 1,
+                minor: 72,
+                patch: Some(0),
+                number: Some(3)
+            }
+        );
+        assert!(Channel::from_str("1").is_err());
+        assert!(Channel::from_str("1.72-alpha").is_err());
+    }
+
+    #[test]
+    fn custom_channels_must_be_marked() {
+        let custom = Channel::from_str("custom:corp-tools").unwrap();
+        assert_eq!(custom, Channel::custom("corp-tools").unwrap());
+        assert_eq!(custom.to_string(), "custom:corp-tools");
+        assert_eq!(custom.manifest_name(), "corp-tools");
+        for name in ["stabel", "foo.toml", "", "corp/tools"] {
+            assert!(Channel::from_str(name).is_err(), "{name}");
+        }
+        for name in ["stable", "nightly-2023", "foo.toml", "corp/tools", ""] {
+            assert!(Channel::custom(name).is_err(), "{name}");
+        }
+        assert!(Channel::custom("stableish").is_ok());
+    }
+
+    #[test]
+    fn parses_toolchains() {
+        let toolchain = Toolchain::from_str("1.72.0-beta.3-2023-06-01-x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(toolchain.channel.to_string(), "1.72.0-beta.3");
+        assert_eq!(toolchain.date.unwrap().to_string(), "2023-06-01");
+        assert_eq!(toolchain.host.unwrap().to_string(), "x86_64-unknown-linux-gnu");
+
+        let toolchain = Toolchain::from_str("custom:corp-tools-2023-06-01").unwrap();
+        assert_eq!(toolchain.channel, Channel::Custom("corp-tools".to_string()));
+        assert_eq!(
+            toolchain.manifest_path(),
+            "dist/2023-06-01/channel-rust-corp-tools.toml"
+        );
+        assert_eq!(toolchain.to_string(), "custom:corp-tools-2023-06-01");
+
+        assert!(Toolchain::from_str("stabel").is_err());
+        assert!(Toolchain::from_str("stabel-x86_64-unknown-linux-gnu").is_err());
+    }
+
+    fn triple(name: &str) -> Triple {
+        Triple::from_str(name).unwrap()
+    }
+
+    fn complete(name: &str, host: &str) -> Result<String, HostError> {
+        PartialToolchain::from_str(name)
+            .unwrap()
+            .complete(&triple(host))
+            .map(|toolchain| toolchain.to_string())
+    }
+
+    #[test]
+    fn completes_partial_toolchains_from_host() {
+        let host = "x86_64-pc-windows-gnu";
+        assert_eq!(complete("stable-msvc", host).unwrap(), "stable-x86_64-pc-windows-msvc");
+        assert_eq!(
+            complete("1.70-x86_64-pc-windows", host).unwrap(),
+            "1.70-x86_64-pc-windows"
+        );
+        assert_eq!(
+            complete("nightly-gnu", "aarch64-unknown-linux-musl").unwrap(),
+            "nightly-aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            complete("nightly-2023-06-01-i686", host).unwrap(),
+            "nightly-2023-06-01-i686-pc-windows"
+        );
+        assert_eq!(complete("beta", host).unwrap(), "beta");
+        assert_eq!(
+            complete("stable-aarch64-apple-darwin", host).unwrap(),
+            "stable-aarch64-apple-darwin"
+        );
+    }
+
+    #[test]
+    fn completes_partial_toolchains_from_manifest() {
+        let commit = "871b5952023139738f72eba235063575062bc2e9";
+        let mut text = manifest_text("2023-06-01", "1.70.0", commit, "x86_64-unknown-linux-gnu", &[]);
+        for host in [
+            "x86_64-pc-windows-msvc",
+            "x86_64-pc-windows-gnu",
+            "i686-pc-windows-msvc",
+        ] {
+            let _ = write!(text, "[pkg.rust.target.{}]\navailable = true\n", host);
+        }
+        let manifest = Manifest::try_from(text.as_str()).unwrap();
+        let complete_from = |name: &str, host: &str| {
+            PartialToolchain::from_str(name)
+                .unwrap()
+                .complete_from(&triple(host), &manifest)
+                .map(|toolchain| toolchain.to_string())
+        };
+        let host = "x86_64-unknown-linux-gnu";
+        assert_eq!(
+            complete_from("stable-msvc", host).unwrap(),
+            "stable-x86_64-pc-windows-msvc"
+        );
+        assert_eq!(
+            complete_from("stable-i686", host).unwrap(),
+            "stable-i686-pc-windows-msvc"
+        );
+        // The architecture and environment are both shared with the host
+        assert_eq!(
+            complete_from("stable-pc-windows", host).unwrap(),
+            "stable-x86_64-pc-windows-gnu"
+        );
+        assert_eq!(complete_from("stable", host).unwrap(), "stable");
+
+        match complete_from("stable-pc-windows", "aarch64-apple-darwin") {
+            Err(HostError::Ambiguous(partial, candidates)) => {
+                assert_eq!(partial, "pc-windows");
+                assert_eq!(candidates.len(), 3);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            complete_from("stable-musl", host),
+            Err(HostError::NoMatch(partial)) if partial == "musl"
+        ));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::hash_value::AbbreviatedHash;
+use crate::toolchain::Channel;
+use crate::HashValue;
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The release channel a package version was built for, as indicated by its
+/// pre-release identifier
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ReleaseChannel {
+    /// A nightly build (`-nightly`)
+    Nightly,
+
+    /// A beta build (`-beta` or `-beta.N`)
+    Beta(Option<u64>),
+
+    /// A stable release (no pre-release identifier)
+    Stable,
+
+    /// Any other pre-release identifier (e.g. `-dev`)
+    Other(String),
+}
+
+impl ReleaseChannel {
+    fn from_pre_release(pre: &semver::Prerelease) -> ReleaseChannel {
+        match pre.as_str() {
+            "" => ReleaseChannel::Stable,
+            "nightly" => ReleaseChannel::Nightly,
+            "beta" => ReleaseChannel::Beta(None),
+            other => other
+                .strip_prefix("beta.")
+                .and_then(|number| u64::from_str(number).ok())
+                .map_or_else(
+                    || ReleaseChannel::Other(other.to_string()),
+                    |n| ReleaseChannel::Beta(Some(n)),
+                ),
+        }
+    }
+
+    /// Orders channels in the sequence a version passes through them. Unknown
+    /// pre-release identifiers are treated as preceding nightly.
+    fn rank(&self) -> (u8, u64) {
+        match self {
+            ReleaseChannel::Other(_) => (0, 0),
+            ReleaseChannel::Nightly => (1, 0),
+            ReleaseChannel::Beta(number) => (2, number.unwrap_or_default()),
+            ReleaseChannel::Stable => (3, 0),
+        }
+    }
+}
+
+/// Errors which can occur while parsing a package version string
+#[derive(Debug, Error)]
+pub enum ParseError {
+    /// The version number was not a valid semantic version
+    #[error("Invalid version number: {0}")]
+    Version(#[from] semver::Error),
+
+    /// The parenthesised build information was malformed
+    #[error("Invalid build information: {0}")]
+    BuildInfo(String),
+}
+
+/// A parsed package version string, such as
+/// `1.67.0-nightly (a1b2c3d4e 2022-11-30)` or `0.1.67 (a1b2c3d4e 2022-11-30)`.
+///
+/// Versions are ordered by version number, then by channel (nightly, then
+/// beta, then stable), then by build date.
+#[derive(Clone, Debug)]
+pub struct PackageVersion {
+    /// The version number, including any pre-release identifier
+    pub version: semver::Version,
+
+    /// The abbreviated commit hash the package was built from, if present
+    pub commit: Option<AbbreviatedHash>,
+
+    /// The date of the commit the package was built from, if present
+    pub date: Option<NaiveDate>,
+}
+
+/// An inconsistency between a package version string and the rest of the
+/// manifest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VersionInconsistency {
+    /// The abbreviated commit hash did not prefix the package's git commit
+    CommitMismatch {
+        /// The abbreviated hash in the version string
+        short: AbbreviatedHash,
+
+        /// The package's git commit
+        git_commit: HashValue,
+    },
+
+    /// The build date was later than the manifest date
+    DateAfterManifest {
+        /// The build date in the version string
+        date: NaiveDate,
+
+        /// The manifest date
+        manifest_date: NaiveDate,
+    },
+}
+
+impl std::fmt::Display for VersionInconsistency {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            VersionInconsistency::CommitMismatch { short, git_commit } => {
+                write!(formatter, "commit {} does not match git commit {}", short, git_commit)
+            }
+            VersionInconsistency::DateAfterManifest { date, manifest_date } => {
+                write!(
+                    formatter,
+                    "build date {} is after manifest date {}",
+                    date, manifest_date
+                )
+            }
+        }
+    }
+}
+
+impl PackageVersion {
+    /// The release channel indicated by the pre-release identifier
+    #[must_use]
+    pub fn channel(&self) -> ReleaseChannel {
+        ReleaseChannel::from_pre_release(&self.version.pre)
+    }
+
+    /// Checks the version string against the package's git commit and the
+    /// date of the manifest it was listed in. The build date is the date of
+    /// the commit, so is normally the day before a nightly manifest's date and
+    /// may be well before a stable one's, but is never after it.
+    #[must_use]
+    pub fn check(&self, git_commit: &HashValue, manifest_date: NaiveDate) -> Vec<VersionInconsistency> {
+        let mut inconsistencies = Vec::new();
+        if let Some(short) = &self.commit {
+            if !short.is_prefix_of(git_commit) {
+                inconsistencies.push(VersionInconsistency::CommitMismatch {
+                    short: short.clone(),
+                    git_commit: git_commit.clone(),
+                });
+            }
+        }
+        if let Some(date) = self.date.filter(|date| *date > manifest_date) {
+            inconsistencies.push(VersionInconsistency::DateAfterManifest { date, manifest_date });
+        }
+        inconsistencies
+    }
+
+    /// The versioned channel this package version was released on (e.g.
+    /// `1.72.0-nightly` or `1.70.0-beta.3`), or `None` for unknown pre-release
+    /// identifiers or version numbers too large for a `Channel`
+    #[must_use]
+    pub fn to_channel(&self) -> Option<Channel> {
+        let major = u16::try_from(self.version.major).ok()?;
+        let minor = u16::try_from(self.version.minor).ok()?;
+        let patch = Some(u16::try_from(self.version.patch).ok()?);
+        match self.channel() {
+            ReleaseChannel::Stable => Some(Channel::Version(major, minor, patch)),
+            ReleaseChannel::Beta(number) => Some(Channel::VersionBeta {
+                major,
+                minor,
+                patch,
+                number: number.map(u16::try_from).transpose().ok()?,
+            }),
+            ReleaseChannel::Nightly => Some(Channel::VersionNightly { major, minor, patch }),
+            ReleaseChannel::Other(_) => None,
+        }
+    }
+
+    fn sort_key(&self) -> impl Ord + '_ {
+        (
+            (self.version.major, self.version.minor, self.version.patch),
+            self.channel().rank(),
+            self.date,
+            self.version.pre.as_str(),
+            self.commit.as_ref(),
+            self.version.build.as_str(),
+        )
+    }
+}
+
+impl FromStr for PackageVersion {
+    type Err = ParseError;
+
+    fn from_str(string: &str) -> Result<PackageVersion, ParseError> {
+        let string = string.trim();
+        let (version, build_info) = match string.split_once('(') {
+            Some((version, rest)) => {
+                let build_info = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| ParseError::BuildInfo(rest.to_string()))?;
+                (version.trim(), Some(build_info))
+            }
+            None => (string, None),
+        };
+        let mut result = PackageVersion {
+            version: semver::Version::parse(version)?,
+            commit: None,
+            date: None,
+        };
+        for token in build_info.unwrap_or_default().split_whitespace() {
+            if let Ok(date) = NaiveDate::from_str(token) {
+                result.date = Some(date);
+            } else if let Some(commit) = AbbreviatedHash::from_str(token)
+                .ok()
+                .filter(|_| result.commit.is_none())
+            {
+                result.commit = Some(commit);
+            } else {
+                return Err(ParseError::BuildInfo(token.to_string()));
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl std::fmt::Display for PackageVersion {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(formatter, "{}", self.version)?;
+        let build_info: Vec<String> = self
+            .commit
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.date.map(|date| date.to_string()))
+            .collect();
+        if !build_info.is_empty() {
+            write!(formatter, " ({})", build_info.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for PackageVersion {
+    fn eq(&self, other: &PackageVersion) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PackageVersion {}
+
+impl PartialOrd for PackageVersion {
+    fn partial_cmp(&self, other: &PackageVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PackageVersion {
+    fn cmp(&self, other: &PackageVersion) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(string: &str) -> PackageVersion {
+        PackageVersion::from_str(string).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, day).unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        let stable = version("1.70.0 (90c541806 2023-05-31)");
+        assert_eq!(stable.version, semver::Version::new(1, 70, 0));
+        assert_eq!(stable.channel(), ReleaseChannel::Stable);
+        assert_eq!(stable.commit, Some(AbbreviatedHash::from_str("90c541806").unwrap()));
+        assert_eq!(stable.date, NaiveDate::from_ymd_opt(2023, 5, 31));
+        assert_eq!(stable.to_channel(), Some(Channel::Version(1, 70, Some(0))));
+
+        let beta = version("1.71.0-beta.3 (C7D6F8C1F 2023-06-20)");
+        assert_eq!(beta.channel(), ReleaseChannel::Beta(Some(3)));
+        assert_eq!(beta.to_string(), "1.71.0-beta.3 (c7d6f8c1f 2023-06-20)");
+
+        let nightly = version("1.72.0-nightly (871b59520 2023-06-01)");
+        assert_eq!(nightly.channel(), ReleaseChannel::Nightly);
+        assert_eq!(nightly.to_string(), "1.72.0-nightly (871b59520 2023-06-01)");
+
+        let bare = version("0.1.72");
+        assert_eq!((bare.commit, bare.date), (None, None));
+        assert_eq!(
+            version("1.72.0-dev").channel(),
+            ReleaseChannel::Other("dev".to_string())
+        );
+
+        assert!(matches!(
+            PackageVersion::from_str("1.70 (90c541806)"),
+            Err(ParseError::Version(_))
+        ));
+        assert!(matches!(
+            PackageVersion::from_str("1.70.0 (90c541806"),
+            Err(ParseError::BuildInfo(_))
+        ));
+        assert!(matches!(
+            PackageVersion::from_str("1.70.0 (90c541806 abcdef123)"),
+            Err(ParseError::BuildInfo(token)) if token == "abcdef123"
+        ));
+    }
+
+    #[test]
+    fn orders_versions_by_channel_then_date() {
+        let ordered = [
+            "1.69.0 (84c898d65 2023-04-16)",
+            "1.70.0-nightly (9aa5c24b7 2023-03-05)",
+            "1.70.0-nightly (8b1fa8e1b 2023-03-06)",
+            "1.70.0-beta (a1b2c3d4e 2023-04-18)",
+            "1.70.0-beta.2 (90c541806 2023-04-25)",
+            "1.70.0-beta.10 (90c541806 2023-05-25)",
+            "1.70.0 (90c541806 2023-05-31)",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn checks_commit_and_date() {
+        let git_commit = HashValue::from_str("871b5952023139738f72eba235063575062bc2e9").unwrap();
+        assert!(version("1.72.0-nightly (871B59520 2023-05-31)")
+            .check(&git_commit, date(1))
+            .is_empty());
+        assert!(version("1.72.0-nightly").check(&git_commit, date(1)).is_empty());
+
+        assert_eq!(
+            version("1.72.0-nightly (abcdef123 2023-05-31)").check(&git_commit, date(1)),
+            [VersionInconsistency::CommitMismatch {
+                short: AbbreviatedHash::from_str("abcdef123").unwrap(),
+                git_commit: git_commit.clone(),
+            }]
+        );
+        assert_eq!(
+            version("1.72.0-nightly (871b59520 2023-06-02)").check(&git_commit, date(1)),
+            [VersionInconsistency::DateAfterManifest {
+                date: date(2),
+                manifest_date: date(1),
+            }]
+        );
+    }
+}